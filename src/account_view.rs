@@ -0,0 +1,120 @@
+use anchor_lang::Discriminator;
+use raydium_amm_v3::states::PersonalPositionState;
+use solana_sdk::pubkey::Pubkey;
+use crate::error::{BalanceFetcherError, Result};
+
+// PersonalPositionState layout: discriminator (8) + bump (1) + nft_mint (32) + pool_id (32)
+// + tick_lower_index (4) + tick_upper_index (4) + liquidity (16) + ...
+pub(crate) const NFT_MINT_OFFSET: usize = 8 + 1;
+pub(crate) const POOL_ID_OFFSET: usize = NFT_MINT_OFFSET + 32;
+const TICK_LOWER_INDEX_OFFSET: usize = POOL_ID_OFFSET + 32;
+const TICK_UPPER_INDEX_OFFSET: usize = TICK_LOWER_INDEX_OFFSET + 4;
+const LIQUIDITY_OFFSET: usize = TICK_UPPER_INDEX_OFFSET + 4;
+const MIN_POSITION_LEN: usize = LIQUIDITY_OFFSET + 16;
+
+/// Offset and length of the `dataSlice` of a position account covering its tick range and liquidity
+pub const POSITION_RANGE_SLICE: (usize, usize) = (TICK_LOWER_INDEX_OFFSET, MIN_POSITION_LEN - TICK_LOWER_INDEX_OFFSET);
+
+/// Tick range and liquidity of a position
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PositionRange {
+    pub tick_lower_index: i32,
+    pub tick_upper_index: i32,
+    pub liquidity: u128,
+}
+
+impl PositionRange {
+    /// Read from position account data sliced with `POSITION_RANGE_SLICE`
+    pub fn from_slice(data: &[u8]) -> Result<Self> {
+        if data.len() != POSITION_RANGE_SLICE.1 {
+            return Err(BalanceFetcherError::Deserialize(format!(
+                "position range data must be {} bytes, got {}",
+                POSITION_RANGE_SLICE.1,
+                data.len()
+            )));
+        }
+        Ok(Self {
+            tick_lower_index: i32::from_le_bytes(data[0..4].try_into().unwrap()),
+            tick_upper_index: i32::from_le_bytes(data[4..8].try_into().unwrap()),
+            liquidity: u128::from_le_bytes(data[8..24].try_into().unwrap()),
+        })
+    }
+}
+
+/// Zero-copy view over the data of a `PersonalPositionState` account
+///
+/// Fields are read in place from the borrowed data instead of deserializing the whole account, which matters
+/// when processing large sets of position accounts, e.g. from an indexer.
+pub struct PositionView<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> PositionView<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self> {
+        if data.len() < MIN_POSITION_LEN {
+            let message = format!("position account data too short: {} bytes", data.len());
+            return Err(BalanceFetcherError::Deserialize(message));
+        }
+        if data[..8] != PersonalPositionState::DISCRIMINATOR {
+            return Err(BalanceFetcherError::Deserialize("account is not a Raydium position".to_string()));
+        }
+        Ok(Self { data })
+    }
+
+    pub fn nft_mint(&self) -> Pubkey {
+        Pubkey::new_from_array(self.array(NFT_MINT_OFFSET))
+    }
+
+    pub fn pool_id(&self) -> Pubkey {
+        Pubkey::new_from_array(self.array(POOL_ID_OFFSET))
+    }
+
+    pub fn tick_lower_index(&self) -> i32 {
+        i32::from_le_bytes(self.array(TICK_LOWER_INDEX_OFFSET))
+    }
+
+    pub fn tick_upper_index(&self) -> i32 {
+        i32::from_le_bytes(self.array(TICK_UPPER_INDEX_OFFSET))
+    }
+
+    pub fn liquidity(&self) -> u128 {
+        u128::from_le_bytes(self.array(LIQUIDITY_OFFSET))
+    }
+
+    fn array<const N: usize>(&self, offset: usize) -> [u8; N] {
+        // The length is checked in `new`
+        self.data[offset..offset + N].try_into().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::AccountSerialize;
+
+    #[test]
+    fn test_position_view() {
+        let position = PersonalPositionState {
+            nft_mint: Pubkey::new_unique(),
+            pool_id: Pubkey::new_unique(),
+            tick_lower_index: -120,
+            tick_upper_index: 60,
+            liquidity: u128::MAX - 1,
+            ..PersonalPositionState::default()
+        };
+        let mut data = Vec::new();
+        position.try_serialize(&mut data).unwrap();
+        let view = PositionView::new(&data).unwrap();
+        assert_eq!(view.nft_mint(), position.nft_mint);
+        assert_eq!(view.pool_id(), position.pool_id);
+        assert_eq!(view.tick_lower_index(), -120);
+        assert_eq!(view.tick_upper_index(), 60);
+        assert_eq!(view.liquidity(), u128::MAX - 1);
+        let (offset, length) = POSITION_RANGE_SLICE;
+        let range = PositionRange::from_slice(&data[offset..offset + length]).unwrap();
+        assert_eq!(range, PositionRange { tick_lower_index: -120, tick_upper_index: 60, liquidity: u128::MAX - 1 });
+        assert!(PositionRange::from_slice(&data[offset..offset + length - 1]).is_err());
+        assert!(PositionView::new(&data[..MIN_POSITION_LEN - 1]).is_err());
+        assert!(PositionView::new(&[0; MIN_POSITION_LEN]).is_err());
+    }
+}
@@ -0,0 +1,223 @@
+use std::str::FromStr;
+use serde_json::json;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_rpc_client_api::config::{RpcAccountInfoConfig, RpcTokenAccountsFilter};
+use solana_rpc_client_api::request::{RpcRequest, MAX_MULTIPLE_ACCOUNTS};
+use solana_rpc_client_api::response::{Response, RpcKeyedAccount};
+use solana_sdk::account::Account;
+use solana_sdk::pubkey::Pubkey;
+use spl_token_2022::extension::StateWithExtensions;
+use crate::balance_fetcher::{
+    classify_ownership, deserialize_anchor_account, mint_token_program, missing_spl_token, pool_positions_from_accounts,
+    position_nfts_from_token_accounts, raydium_positions, spl_token_from_account, total_position_amounts,
+    AccountEncoding, BalanceFetcher, Ownership, PositionNftTokenInfo, SPLToken, RAYDIUM_V3_PROGRAM_ID, SOL_DECIMALS,
+};
+use crate::cache::{AccountCache, AccountKind, CacheTtls};
+use crate::report::Amount;
+use crate::error::Result;
+use crate::ids::{MintAddress, PoolId};
+
+/// Async counterpart of `BalanceFetcher`, for embedding in tokio services
+///
+/// Accounts are parsed by the same code as `BalanceFetcher`, so both return the same results. Use
+/// `BalanceFetcher::builder(..).build_async()` for the same retries, rate limit, failover and cache.
+pub struct AsyncBalanceFetcher {
+    pub rpc: RpcClient,
+    account_encoding: AccountEncoding,
+    cache: Option<AccountCache>,
+}
+
+impl AsyncBalanceFetcher {
+    /// Query `rpc_url` with the defaults of `BalanceFetcher::builder`
+    ///
+    /// # Panics
+    /// If the HTTP client cannot be built, see `try_new`.
+    pub fn new<T: ToString>(rpc_url: T) -> Self {
+        Self::try_new(rpc_url).expect("build async balance fetcher")
+    }
+
+    /// Same as `new`, returning `BalanceFetcherError::Http` if the HTTP client cannot be built
+    pub fn try_new<T: ToString>(rpc_url: T) -> Result<Self> {
+        BalanceFetcher::builder(rpc_url).build_async()
+    }
+
+    /// Use an already configured nonblocking RPC client, see `BalanceFetcher::builder` for the common options
+    pub fn from_rpc(rpc: RpcClient) -> Self {
+        Self { rpc, account_encoding: AccountEncoding::default(), cache: None }
+    }
+
+    /// Set the encoding of account data requested for position lookups
+    pub fn with_account_encoding(mut self, account_encoding: AccountEncoding) -> Self {
+        self.account_encoding = account_encoding;
+        self
+    }
+
+    /// Reuse fetched mints and position PDAs of wallets until their TTL passes, see `BalanceFetcher::with_cache`
+    pub fn with_cache(mut self, ttls: CacheTtls) -> Self {
+        self.cache = Some(AccountCache::new(ttls));
+        self
+    }
+
+    /// Fetch the SOL balance of a wallet, see `BalanceFetcher::balance_sol`
+    pub async fn balance_sol(&self, wallet_address: &Pubkey) -> Result<u64> {
+        let balance = self.rpc.get_balance(wallet_address).await?;
+        Ok(balance)
+    }
+
+    /// Fetch the WSOL (Wrapped SOL) balance of a wallet, see `BalanceFetcher::balance_wsol`
+    pub async fn balance_wsol(&self, wallet_address: &Pubkey) -> Result<u64> {
+        let balance = self.balance_spl_token(wallet_address, &MintAddress::wsol()).await?;
+        Ok(balance.amount)
+    }
+
+    /// Fetch the SOL and WSOL (Wrapped SOL) balance sum of a wallet, see `BalanceFetcher::balance_sol_unified`
+    pub async fn balance_sol_unified(&self, wallet_address: &Pubkey) -> Result<u64> {
+        let wsol_mint_address = MintAddress::wsol();
+        let (sol_balance, wsol) = tokio::try_join!(
+            self.balance_sol(wallet_address),
+            self.balance_spl_token(wallet_address, &wsol_mint_address)
+        )?;
+        let sol_balance = Amount { amount: sol_balance, decimals: SOL_DECIMALS };
+        Ok(sol_balance.checked_add(&Amount { amount: wsol.amount, decimals: wsol.decimals })?.amount)
+    }
+
+    /// Fetch the balance of a SPL token account, see `BalanceFetcher::balance_spl_token`
+    pub async fn balance_spl_token(
+        &self,
+        wallet_address: &Pubkey,
+        token_mint_address: &MintAddress,
+    ) -> Result<SPLToken> {
+        let mint = self.get_account_cached(token_mint_address, AccountKind::Mint).await?;
+        let token_program = mint_token_program(token_mint_address, &mint)?;
+        let addr = spl_associated_token_account::get_associated_token_address_with_program_id(
+            wallet_address,
+            token_mint_address,
+            &token_program,
+        );
+        let Some(account) = self.rpc.get_account_with_commitment(&addr, self.rpc.commitment()).await?.value else {
+            log::warn!("address {} does not have token account for SPL token {}", addr, token_mint_address);
+            return missing_spl_token(&mint);
+        };
+        let ownership = self.account_ownership(wallet_address, &account).await?;
+        spl_token_from_account(&account, &mint, ownership)
+    }
+
+    /// Detect who can spend a token account of a wallet, see `BalanceFetcher::token_account_ownership`
+    pub async fn token_account_ownership(&self, wallet_address: &Pubkey, token_account: &Pubkey) -> Result<Ownership> {
+        let account = self.rpc.get_account(token_account).await?;
+        self.account_ownership(wallet_address, &account).await
+    }
+
+    async fn account_ownership(&self, wallet_address: &Pubkey, account: &Account) -> Result<Ownership> {
+        let owner = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data)?.base.owner;
+        let owner_account = self.rpc.get_account_with_commitment(&owner, self.rpc.commitment()).await?.value;
+        Ok(classify_ownership(wallet_address, &owner, owner_account.as_ref()))
+    }
+
+    /// Fetch LP position amounts of Raydium SOL-USDC.1bp pool, see `BalanceFetcher::position_sol_usdc_1bp`
+    pub async fn position_sol_usdc_1bp(&self, wallet_address: &Pubkey) -> Result<(u64, u64)> {
+        self.raydium_pool_position(wallet_address, &PoolId::sol_usdc_1bp()).await
+    }
+
+    /// Fetch LP position amounts of Raydium pool, see `BalanceFetcher::raydium_pool_position`
+    pub async fn raydium_pool_position(&self, wallet_address: &Pubkey, pool_id: &PoolId) -> Result<(u64, u64)> {
+        let pdas = self.wallet_position_pdas(wallet_address).await?;
+        let accounts = self.get_multiple_accounts_chunked(&pdas).await?;
+        let positions = pool_positions_from_accounts(&pdas, accounts, pool_id);
+        let pool_account = self.get_account_cached(pool_id, AccountKind::Pool).await?;
+        let pool_state = deserialize_anchor_account::<raydium_amm_v3::states::PoolState>(&pool_account)?;
+        total_position_amounts(&raydium_positions(&positions, &pool_state)?)
+    }
+
+    async fn wallet_position_pdas(&self, wallet_address: &Pubkey) -> Result<Vec<Pubkey>> {
+        if let Some(positions) = self.cache.as_ref().and_then(|cache| cache.position_pdas(wallet_address)) {
+            return Ok(positions);
+        }
+        let raydium_v3_program = Pubkey::from_str(RAYDIUM_V3_PROGRAM_ID).unwrap();
+        let positions: Vec<Pubkey> = self
+            .get_nft_account_and_position_by_owner(wallet_address, spl_token_2022::id(), &raydium_v3_program)
+            .await?
+            .iter()
+            .map(|item| item.position)
+            .collect();
+        if let Some(cache) = &self.cache {
+            cache.insert_position_pdas(*wallet_address, positions.clone());
+        }
+        Ok(positions)
+    }
+
+    /// Fetch an account, from the cache if it is enabled and has the account fresh for its kind
+    async fn get_account_cached(&self, address: &Pubkey, kind: AccountKind) -> Result<Account> {
+        if let Some(account) = self.cache.as_ref().and_then(|cache| cache.account(address, kind)) {
+            return Ok(account);
+        }
+        let account = self.rpc.get_account(address).await?;
+        if let Some(cache) = &self.cache {
+            cache.insert_account(*address, account.clone());
+        }
+        Ok(account)
+    }
+
+    /// Fetch any number of accounts, in as few `getMultipleAccounts` requests as the RPC limit allows
+    async fn get_multiple_accounts_chunked(&self, addresses: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        let mut accounts = Vec::with_capacity(addresses.len());
+        for chunk in addresses.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            accounts.extend(self.rpc.get_multiple_accounts(chunk).await?);
+        }
+        Ok(accounts)
+    }
+
+    /// Fetch the position NFTs of an owner, see `BalanceFetcher::get_nft_account_and_position_by_owner`
+    pub async fn get_nft_account_and_position_by_owner(
+        &self,
+        owner: &Pubkey,
+        token_program: Pubkey,
+        raydium_amm_v3_program: &Pubkey,
+    ) -> Result<Vec<PositionNftTokenInfo>> {
+        let config = RpcAccountInfoConfig {
+            encoding: Some(self.account_encoding.into()),
+            commitment: Some(self.rpc.commitment()),
+            ..RpcAccountInfoConfig::default()
+        };
+        let all_tokens: Response<Vec<RpcKeyedAccount>> = self.rpc.send(
+            RpcRequest::GetTokenAccountsByOwner,
+            json!([
+                owner.to_string(),
+                RpcTokenAccountsFilter::ProgramId(token_program.to_string()),
+                config
+            ]),
+        ).await?;
+        position_nfts_from_token_accounts(all_tokens.value, token_program, raydium_amm_v3_program)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_balancer_fetcher() -> AsyncBalanceFetcher {
+        let rpc_url = "https://api.mainnet-beta.solana.com";
+        AsyncBalanceFetcher::new(rpc_url)
+    }
+
+    #[tokio::test]
+    async fn test_balance_sol() {
+        let fetcher = new_balancer_fetcher();
+        // Binance wallet address
+        let pubkey = Pubkey::from_str("5tzFkiKscXHK5ZXCGbXZxdw7gTjjD1mBwuoFbhUvuAi9").unwrap();
+        let balance_sol = fetcher.balance_sol(&pubkey).await.unwrap();
+        assert!(balance_sol > 0);
+        let balance_sol_unified = fetcher.balance_sol_unified(&pubkey).await.unwrap();
+        assert!(balance_sol_unified > balance_sol);
+    }
+
+    #[tokio::test]
+    async fn test_get_raydium_pool_position() {
+        let fetcher = new_balancer_fetcher();
+        let wallet = Pubkey::from_str("53zSj4G935ZY2a5x2UnGAiJXSuXXmGHaLph2zhAUvYpg").unwrap();
+        let pool_id = PoolId::sol_usdc_1bp();
+        let (amount_0, amount_1) = fetcher.raydium_pool_position(&wallet, &pool_id).await.unwrap();
+        assert!(amount_0 > 0);
+        assert!(amount_1 > 0);
+    }
+}
@@ -1,34 +1,400 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::str::FromStr;
-use anchor_lang::AccountDeserialize;
-use raydium_amm_v3::libraries::{get_delta_amount_0_unsigned, get_delta_amount_1_unsigned, tick_math};
-use solana_account_decoder::parse_token::{TokenAccountType, UiAccountState};
-use solana_account_decoder::UiAccountData;
-use solana_client::rpc_client::RpcClient;
-use solana_rpc_client_api::client_error::ErrorKind;
-use solana_rpc_client_api::request::{RpcError, TokenAccountsFilter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use anchor_lang::{AccountDeserialize, Discriminator};
+use serde::Serialize;
+use serde_json::json;
+use solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig};
+use solana_client::rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient};
+use solana_rpc_client_api::config::{
+    RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcTokenAccountsFilter, RpcTransactionConfig,
+};
+use solana_rpc_client_api::filter::{Memcmp, RpcFilterType};
+use solana_rpc_client_api::request::{
+    RpcRequest, MAX_GET_CONFIRMED_SIGNATURES_FOR_ADDRESS2_LIMIT, MAX_MULTIPLE_ACCOUNTS,
+};
+use solana_rpc_client_api::response::{Response, RpcKeyedAccount};
 use solana_sdk::account::Account;
+use solana_sdk::program_pack::Pack;
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::UiTransactionEncoding;
+use spl_token_2022::extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions};
+use crate::account_view::{PositionRange, PositionView, NFT_MINT_OFFSET, POOL_ID_OFFSET, POSITION_RANGE_SLICE};
+use crate::cache::{AccountCache, AccountKind, CacheTtls};
+use crate::error::{BalanceFetcherError, Result};
+use crate::ids::{MintAddress, PoolId};
+use crate::math::{self, PendingReward};
+use crate::report::{serialize_pubkey, Amount};
+use crate::swap_quote::{self, InitializedTick, SwapDirection, SwapQuote};
+use crate::token_metadata::{self, TokenMetadata};
+#[cfg(feature = "jupiter")]
+use crate::jupiter::JupiterClient;
 
-type Result<T> = anyhow::Result<T>;
 pub struct BalanceFetcher {
     pub rpc: RpcClient,
+    account_encoding: AccountEncoding,
+    cache: Option<AccountCache>,
+}
+
+/// Encoding of account data requested from RPC for scans over many accounts
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AccountEncoding {
+    #[default]
+    Base64,
+    /// Zstandard compressed base64, which cuts bandwidth on providers supporting it
+    Base64Zstd,
+}
+
+impl From<AccountEncoding> for UiAccountEncoding {
+    fn from(encoding: AccountEncoding) -> Self {
+        match encoding {
+            AccountEncoding::Base64 => UiAccountEncoding::Base64,
+            AccountEncoding::Base64Zstd => UiAccountEncoding::Base64Zstd,
+        }
+    }
 }
 
 #[allow(dead_code)]
 pub struct SPLToken {
-    amount: u64,
+    pub(crate) amount: u64,
+    /// The decimals of the mint, also when the token account does not exist
+    pub decimals: u8,
+    pub ownership: Ownership,
+    /// The token program owning the token account, Token or Token-2022
+    pub token_program: Pubkey,
+    /// Whether the token account exists, a missing one has no amount
+    pub exists: bool,
+}
+
+/// Who controls a token account, relative to the wallet it was queried for
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Ownership {
+    /// The token account is owned by the queried wallet, which can spend it alone
+    Wallet,
+    /// The token account owner is a SPL token multisig, so spending needs M-of-N signers
+    Multisig { owner: Pubkey },
+    /// The token account owner is not the queried wallet or is a PDA without a private key
+    NonWallet { owner: Pubkey },
+}
+
+/// The largest token accounts of a mint, along with the mint supply
+pub struct MintHolders {
+    pub supply: u64,
+    pub decimals: u8,
+    /// Sorted by amount in descending order, at most 20 accounts as limited by RPC
+    pub holders: Vec<TokenHolder>,
+}
+
+pub struct TokenHolder {
+    pub token_account: Pubkey,
+    pub amount: u64,
+}
+
+/// Supply, decimals and authorities of a mint
+pub struct MintInfo {
+    pub supply: u64,
     pub decimals: u8,
+    /// An active mint authority can inflate the supply at any time
+    pub mint_authority: Option<Pubkey>,
+    pub freeze_authority: Option<Pubkey>,
+}
+
+/// Progress of a long running scan, reported after each step
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScanProgress {
+    pub steps_done: usize,
+    pub steps_total: usize,
+    /// Number of accounts fetched so far
+    pub accounts_fetched: usize,
+    /// Estimated time left, extrapolated from the steps done so far
+    pub eta: Duration,
+}
+
+/// Aggregate statistics of all positions in a Raydium CLMM pool
+pub struct PoolStats {
+    /// Number of position accounts, including emptied ones that are not closed yet
+    pub positions: usize,
+    /// Number of positions with non-zero liquidity
+    pub open_positions: usize,
+    pub total_liquidity: u128,
+    /// Liquidity of positions whose range covers the current tick
+    pub in_range_liquidity: u128,
+    pub tick_current: i32,
+    pub fee_growth_global_0_x64: u128,
+    pub fee_growth_global_1_x64: u128,
+}
+
+impl PoolStats {
+    /// Fraction of the positions' liquidity that is in range and earning fees
+    pub fn in_range_fraction(&self) -> f64 {
+        if self.total_liquidity == 0 {
+            return 0.0;
+        }
+        self.in_range_liquidity as f64 / self.total_liquidity as f64
+    }
 }
 
+/// Current price of a Raydium CLMM pool, as returned by `pool_price`
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PoolPrice {
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub pool_id: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub mint_0: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub mint_1: Pubkey,
+    pub decimals_0: u8,
+    pub decimals_1: u8,
+    pub sqrt_price_x64: u128,
+    pub tick_current: i32,
+    /// How many token 1 one token 0 is worth, adjusted by the tokens' decimals
+    pub price_0_in_1: f64,
+    /// How many token 0 one token 1 is worth, the inverse of `price_0_in_1`
+    pub price_1_in_0: f64,
+}
+
+/// Value of a position if fully withdrawn and swapped into a single token of its pool now
+pub struct PositionExitValue {
+    /// The position PDA
+    pub position: Pubkey,
+    /// Amounts of token 0 and token 1 withdrawn at the current pool price
+    pub amount_0: u64,
+    pub amount_1: u64,
+    /// Total amount of the exit token after swapping the other token in the same pool
+    pub exit_amount: u64,
+    /// Price impact of the swap, 0 if nothing needs to be swapped
+    pub price_impact: f64,
+}
+
+/// Fee paid by a wallet as the fee payer of a transaction
+pub struct TransactionFee {
+    pub signature: Signature,
+    pub block_time: Option<i64>,
+    /// Fee for signatures, charged per signature
+    pub base_fee: u64,
+    /// Fee paid on top of the base fee for compute unit price
+    pub priority_fee: u64,
+}
+
+/// Transaction fees paid by a wallet, newest transaction first
+pub struct FeeSpend {
+    pub transactions: Vec<TransactionFee>,
+}
+
+impl FeeSpend {
+    pub fn base_fee(&self) -> u64 {
+        self.transactions.iter().map(|tx| tx.base_fee).sum()
+    }
+
+    pub fn priority_fee(&self) -> u64 {
+        self.transactions.iter().map(|tx| tx.priority_fee).sum()
+    }
+
+    pub fn total_fee(&self) -> u64 {
+        self.base_fee() + self.priority_fee()
+    }
+
+    /// Total fee per UTC day, oldest day first. Transactions without block time are skipped.
+    pub fn daily_fees(&self) -> Vec<u64> {
+        let mut daily_fees = std::collections::BTreeMap::new();
+        for tx in &self.transactions {
+            if let Some(block_time) = tx.block_time {
+                *daily_fees.entry(block_time.div_euclid(SECONDS_PER_DAY)).or_insert(0) += tx.base_fee + tx.priority_fee;
+            }
+        }
+        daily_fees.into_values().collect()
+    }
+}
+
+/// The outcome of a check of the RPC endpoint, see `BalanceFetcher::check_rpc`
+pub struct RpcCheck {
+    /// The RPC method checked, e.g. "getHealth"
+    pub method: &'static str,
+    /// What the crate needs the method for
+    pub used_for: &'static str,
+    /// Time the request took, retries included
+    pub latency: Duration,
+    /// A summary of the response, or why the check failed
+    pub result: Result<String>,
+}
+
+impl RpcCheck {
+    fn run<F: FnOnce() -> Result<String>>(method: &'static str, used_for: &'static str, check: F) -> Self {
+        let started_at = Instant::now();
+        let result = check();
+        Self { method, used_for, latency: started_at.elapsed(), result }
+    }
+}
+
+/// An associated token account that does not exist yet
+pub struct MissingTokenAccount {
+    pub mint: Pubkey,
+    /// The associated token account address to be created
+    pub address: Pubkey,
+    pub token_program: Pubkey,
+    /// Lamports needed to create the account rent exempt
+    pub rent: u64,
+}
+
+/// Basic information of any account
+pub struct AccountOverview {
+    pub lamports: u64,
+    /// The program owning the account, e.g. the system program for wallets
+    pub owner: Pubkey,
+    pub executable: bool,
+    pub data_len: usize,
+    /// Lamports needed for the account to be rent exempt with its current data length
+    pub rent_exempt_minimum: u64,
+}
+
+impl AccountOverview {
+    pub fn is_rent_exempt(&self) -> bool {
+        self.lamports >= self.rent_exempt_minimum
+    }
+}
+
+/// SOL balance of a wallet as reported by two RPC endpoints, with the slots they were read at
+pub struct BalanceConsistency {
+    pub balance: u64,
+    pub slot: u64,
+    pub other_balance: u64,
+    pub other_slot: u64,
+    /// False if the balances differ although the endpoints are within the allowed slot lag of each other
+    pub consistent: bool,
+}
+
+/// What kind of account an address is, to tell how to report its balances
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AddressKind {
+    /// A system account, or an address without account yet, that holds SOL and owns token accounts
+    Wallet,
+    /// A token account, which holds a single token on behalf of its owner
+    TokenAccount(TokenAccountInfo),
+    /// Any other account, e.g. a mint, a program or a program-owned account
+    Other { owner: Pubkey },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TokenAccountInfo {
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub mint: Pubkey,
+    /// The owner who can spend the tokens
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub owner: Pubkey,
+    pub amount: Amount,
+    /// The token program owning the token account, Token or Token-2022
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub token_program: Pubkey,
+}
+
+/// Balance of a mint summed over all token accounts of a wallet
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TokenBalance {
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub mint: Pubkey,
+    /// The token program of the mint, Token or Token-2022
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub token_program: Pubkey,
+    /// The raw amount, without decimals
+    pub amount: u64,
+    pub decimals: u8,
+    pub token_accounts: Vec<TokenAccountBalance>,
+}
+
+/// Raw amount held in a single token account
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TokenAccountBalance {
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub address: Pubkey,
+    pub amount: u64,
+}
+
+/// LP positions of a wallet in a Raydium CLMM pool, as returned by `all_raydium_positions`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct PoolPositions {
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub pool_id: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub mint_0: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub mint_1: Pubkey,
+    pub positions: Vec<RaydiumPosition>,
+    /// Token 0 in all positions of the pool
+    pub amount_0: Amount,
+    /// Token 1 in all positions of the pool
+    pub amount_1: Amount,
+}
+
+/// An LP position in a Raydium CLMM pool
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct RaydiumPosition {
+    /// The position PDA
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub position: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub nft_mint: Pubkey,
+    pub tick_lower_index: i32,
+    pub tick_upper_index: i32,
+    pub liquidity: u128,
+    /// Token 0 withdrawable at the pool's current price, as summed up by `raydium_pool_position`
+    pub amount_0: Amount,
+    /// Token 1 withdrawable at the pool's current price, as summed up by `raydium_pool_position`
+    pub amount_1: Amount,
+    /// Whether the current price of the pool is within the tick range, so the position earns fees
+    pub in_range: bool,
+}
+
+/// Decimals of native SOL, lamports per SOL
+pub(crate) const SOL_DECIMALS: u8 = 9;
+
 // Program ID for Solana mainnet.
 pub const WSOL_MINT_ADDRESS: &str = "So11111111111111111111111111111111111111112";
 pub const RAYDIUM_V3_PROGRAM_ID: &str = "CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK";
 pub const SOL_USDC_1BP_POOL_ID: &str = "8sLbNZoA1cfnvMJLPfp98ZLAnFSYCFApfJKMbiXNLwxj";
+pub const USDC_MINT_ADDRESS: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+/// Base fee per transaction signature in lamports
+const LAMPORTS_PER_SIGNATURE: u64 = 5000;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Number of tick arrays fetched in the swap direction when quoting a swap, including the current one
+const SWAP_QUOTE_TICK_ARRAYS: i32 = 10;
+
+/// Longest NFT mint prefix a scan of pool positions is partitioned by, 2 bytes make up to 65536 requests
+const MAX_SCAN_PREFIX_LEN: usize = 2;
 
 impl BalanceFetcher {
+    /// Query `rpc_url` with the defaults of `BalanceFetcher::builder`
+    ///
+    /// # Panics
+    /// If the HTTP client cannot be built, e.g. its TLS backend fails to initialise, see `try_new`.
     pub fn new<T: ToString>(rpc_url: T) -> Self {
-        let rpc = RpcClient::new(rpc_url.to_string());
-        Self { rpc }
+        Self::try_new(rpc_url).expect("build balance fetcher")
+    }
+
+    /// Same as `new`, returning `BalanceFetcherError::Http` if the HTTP client cannot be built
+    pub fn try_new<T: ToString>(rpc_url: T) -> Result<Self> {
+        Self::builder(rpc_url).build()
+    }
+
+    /// Use an already configured RPC client, see `BalanceFetcher::builder` for the common options
+    pub fn from_rpc(rpc: RpcClient) -> Self {
+        Self { rpc, account_encoding: AccountEncoding::default(), cache: None }
+    }
+
+    /// Set the encoding of account data for position and pool scans, decompressed transparently if needed
+    pub fn with_account_encoding(mut self, account_encoding: AccountEncoding) -> Self {
+        self.account_encoding = account_encoding;
+        self
+    }
+
+    /// Reuse fetched pools, mints, AMM configs and position PDAs of wallets for repeat calls, e.g. in watch mode
+    pub fn with_cache(mut self, ttls: CacheTtls) -> Self {
+        self.cache = Some(AccountCache::new(ttls));
+        self
     }
 
     /// Fetch the SOL balance of a wallet
@@ -43,214 +409,1478 @@ impl BalanceFetcher {
         Ok(balance)
     }
 
-    /// Fetch the WSOL (Wrapped SOL) balance of a wallet
+    /// Cross-check the SOL balance of a wallet against another RPC endpoint
+    ///
+    /// Balances read at slots further apart than `max_slot_lag` may legitimately differ. Within that margin a
+    /// difference means one of the endpoints is misbehaving, and a warning is logged.
+    ///
+    /// # Arguments
+    /// - `other` - The fetcher of the other RPC endpoint
+    /// - `wallet_address` - The wallet address
+    /// - `max_slot_lag` - Maximum slot difference for which the balances are expected to be equal
+    ///
+    /// # Returns
+    /// - `BalanceConsistency` - The balances and slots from both endpoints and whether they agree
+    pub fn compare_balance_sol(
+        &self,
+        other: &BalanceFetcher,
+        wallet_address: &Pubkey,
+        max_slot_lag: u64,
+    ) -> Result<BalanceConsistency> {
+        let rsp = self.rpc.get_balance_with_commitment(wallet_address, self.rpc.commitment())?;
+        let other_rsp = other.rpc.get_balance_with_commitment(wallet_address, other.rpc.commitment())?;
+        let slot_lag = rsp.context.slot.abs_diff(other_rsp.context.slot);
+        let consistent = rsp.value == other_rsp.value || slot_lag > max_slot_lag;
+        if !consistent {
+            log::warn!(
+                "SOL balance of {} differs between {} ({} at slot {}) and {} ({} at slot {})",
+                wallet_address,
+                self.rpc.url(),
+                rsp.value,
+                rsp.context.slot,
+                other.rpc.url(),
+                other_rsp.value,
+                other_rsp.context.slot,
+            );
+        }
+        Ok(BalanceConsistency {
+            balance: rsp.value,
+            slot: rsp.context.slot,
+            other_balance: other_rsp.value,
+            other_slot: other_rsp.context.slot,
+            consistent,
+        })
+    }
+
+    /// Fetch the WSOL (Wrapped SOL) balance of a wallet
+    ///
+    /// # Arguments
+    /// - `wallet_address` - The wallet address
+    ///
+    /// # Returns
+    /// - `u64` - The WSOL balance of the wallet
+    pub fn balance_wsol(&self, wallet_address: &Pubkey) -> Result<u64> {
+        let balance = self.balance_spl_token(wallet_address, &MintAddress::wsol())?;
+        Ok(balance.amount)
+    }
+
+    /// Fetch the SOL and WSOL (Wrapped SOL) balance sum of a wallet
+    ///
+    /// # Arguments
+    /// - `wallet_address` - The wallet address
+    ///
+    /// # Returns
+    /// - `u64` - The SOL and WSOL balance of the given wallet
+    pub fn balance_sol_unified(&self, wallet_address: &Pubkey) -> Result<u64> {
+        let sol_balance = Amount { amount: self.balance_sol(wallet_address)?, decimals: SOL_DECIMALS };
+        let wsol = self.balance_spl_token(wallet_address, &MintAddress::wsol())?;
+        Ok(sol_balance.checked_add(&Amount { amount: wsol.amount, decimals: wsol.decimals })?.amount)
+    }
+
+    /// Fetch the balance of a SPL token account
+    ///
+    /// The associated token account is derived for the program owning the mint, so that Token-2022 tokens are
+    /// found as well.
+    ///
+    /// # Arguments
+    /// - `wallet_address` - The wallet address
+    /// - `token_mint_address` - The mint address of the token, either owned by Token or Token-2022 program
+    ///
+    /// # Returns
+    /// - `SPLToken` - The balance and decimals of the token account
+    pub fn balance_spl_token(&self, wallet_address: &Pubkey, token_mint_address: &MintAddress) -> Result<SPLToken> {
+        let mint = self.get_account_cached(token_mint_address, AccountKind::Mint)?;
+        let token_program = mint_token_program(token_mint_address, &mint)?;
+        let addr = spl_associated_token_account::get_associated_token_address_with_program_id(
+            wallet_address,
+            token_mint_address,
+            &token_program,
+        );
+        let Some(account) = self.rpc.get_account_with_commitment(&addr, self.rpc.commitment())?.value else {
+            log::warn!("address {} does not have token account for SPL token {}", addr, token_mint_address);
+            return missing_spl_token(&mint);
+        };
+        let ownership = self.account_ownership(wallet_address, &account)?;
+        spl_token_from_account(&account, &mint, ownership)
+    }
+
+    /// Fetch the balances of all SPL tokens of a wallet, of both the Token and Token-2022 programs
+    ///
+    /// Unlike `balance_spl_token`, every token account of the wallet counts, not only associated token accounts.
+    ///
+    /// # Arguments
+    /// - `wallet_address` - The wallet address
+    ///
+    /// # Returns
+    /// - `Vec<TokenBalance>` - The balance of each mint the wallet has a token account of, sorted by mint
+    pub fn all_token_balances(&self, wallet_address: &Pubkey) -> Result<Vec<TokenBalance>> {
+        let mut balances: BTreeMap<Pubkey, TokenBalance> = BTreeMap::new();
+        for token_program in [spl_token::id(), spl_token_2022::id()] {
+            let filter = RpcTokenAccountsFilter::ProgramId(token_program.to_string());
+            add_token_accounts(&mut balances, self.get_token_accounts_by_owner(wallet_address, filter)?)?;
+        }
+
+        // Decimals live in the mints, fetch them all at once
+        let mints: Vec<Pubkey> = balances.keys().copied().collect();
+        let (_, mint_accounts) = self.get_multiple_accounts_chunked(&mints)?;
+        for (balance, mint_account) in balances.values_mut().zip(mint_accounts) {
+            let decimals = mint_account.and_then(|account| {
+                let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&account.data).ok()?;
+                Some(mint.base.decimals)
+            });
+            match decimals {
+                Some(decimals) => balance.decimals = decimals,
+                None => log::warn!("mint {} cannot be unpacked, assuming 0 decimals", balance.mint),
+            }
+        }
+        Ok(balances.into_values().collect())
+    }
+
+    /// Fetch the balance of a SPL token summed over all token accounts of a wallet
+    ///
+    /// Unlike `balance_spl_token`, tokens held in auxiliary token accounts besides the associated one count too.
+    ///
+    /// # Arguments
+    /// - `wallet_address` - The wallet address
+    /// - `token_mint_address` - The mint address of the token, either owned by Token or Token-2022 program
+    ///
+    /// # Returns
+    /// - `TokenBalance` - The total and the balance of each token account, with no account if the wallet has none
+    pub fn balance_spl_token_all_accounts(
+        &self,
+        wallet_address: &Pubkey,
+        token_mint_address: &MintAddress,
+    ) -> Result<TokenBalance> {
+        let mint = self.get_account_cached(token_mint_address, AccountKind::Mint)?;
+        let token_program = mint_token_program(token_mint_address, &mint)?;
+        let decimals = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint.data)?.base.decimals;
+        let filter = RpcTokenAccountsFilter::Mint(token_mint_address.to_string());
+        let mut balances = BTreeMap::new();
+        add_token_accounts(&mut balances, self.get_token_accounts_by_owner(wallet_address, filter)?)?;
+        let mut balance = balances.remove(&token_mint_address.pubkey()).unwrap_or_else(|| TokenBalance {
+            mint: token_mint_address.pubkey(),
+            token_program,
+            amount: 0,
+            decimals: 0,
+            token_accounts: vec![],
+        });
+        balance.decimals = decimals;
+        Ok(balance)
+    }
+
+    /// Resolve mints to their names and symbols from their Metaplex token metadata accounts
+    ///
+    /// Metadata accounts are cached like other accounts when the cache is enabled, so that listing the same mints
+    /// again takes no request.
+    ///
+    /// # Arguments
+    /// - `mints` - The mint addresses
+    ///
+    /// # Returns
+    /// - `Vec<Option<TokenMetadata>>` - The metadata in the order of `mints`, `None` for a mint without or with an
+    ///   unreadable metadata account
+    pub fn token_metadata(&self, mints: &[Pubkey]) -> Result<Vec<Option<TokenMetadata>>> {
+        let addresses: Vec<Pubkey> = mints.iter().map(token_metadata::metadata_address).collect();
+        let mut accounts: Vec<Option<Account>> = addresses
+            .iter()
+            .map(|address| self.cache.as_ref().and_then(|cache| cache.account(address, AccountKind::Metadata)))
+            .collect();
+
+        // Fetch only the metadata missing from the cache, all at once
+        let missing: Vec<usize> = (0..addresses.len()).filter(|&i| accounts[i].is_none()).collect();
+        let missing_addresses: Vec<Pubkey> = missing.iter().map(|&i| addresses[i]).collect();
+        let (_, fetched) = self.get_multiple_accounts_chunked(&missing_addresses)?;
+        for (i, account) in missing.into_iter().zip(fetched) {
+            if let (Some(cache), Some(account)) = (&self.cache, &account) {
+                cache.insert_account(addresses[i], account.clone());
+            }
+            accounts[i] = account;
+        }
+
+        Ok(accounts
+            .into_iter()
+            .zip(mints)
+            .map(|(account, mint)| {
+                let metadata = TokenMetadata::from_account_data(&account?.data);
+                if let Err(err) = &metadata {
+                    log::warn!("metadata of mint {} cannot be read: {}", mint, err);
+                }
+                metadata.ok()
+            })
+            .collect())
+    }
+
+    /// Detect who can spend a token account of a wallet
+    ///
+    /// # Arguments
+    /// - `wallet_address` - The wallet address the token account is queried for
+    /// - `token_account` - The token account address
+    ///
+    /// # Returns
+    /// - `Ownership` - Whether the wallet, a multisig or another address owns the token account
+    pub fn token_account_ownership(&self, wallet_address: &Pubkey, token_account: &Pubkey) -> Result<Ownership> {
+        let account = self.rpc.get_account(token_account)?;
+        self.account_ownership(wallet_address, &account)
+    }
+
+    fn account_ownership(&self, wallet_address: &Pubkey, account: &Account) -> Result<Ownership> {
+        let owner = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data)?.base.owner;
+        // The owner may not exist on chain at all (e.g. a fresh wallet), which is not a multisig.
+        let owner_account = self.rpc.get_account_with_commitment(&owner, self.rpc.commitment())?.value;
+        Ok(classify_ownership(wallet_address, &owner, owner_account.as_ref()))
+    }
+
+    /// Fetch the SOL a wallet spent on transaction fees, from its transaction history
+    ///
+    /// Only transactions paid by the wallet are counted, failed ones included as they are charged as well.
+    /// Pass the newest signature of a previous call as `until` to only count fees spent since then.
+    ///
+    /// # Arguments
+    /// - `wallet_address` - The wallet address
+    /// - `until` - Stop at this signature (exclusive), or go back as far as `limit` allows if `None`
+    /// - `limit` - Maximum number of recent transactions to look at, their signatures fetched 1000 per request
+    ///
+    /// # Returns
+    /// - `FeeSpend` - Base and priority fees of each transaction paid by the wallet
+    pub fn fee_spend(&self, wallet_address: &Pubkey, until: Option<Signature>, limit: usize) -> Result<FeeSpend> {
+        self.fee_spend_cancellable(wallet_address, until, limit, &AtomicBool::new(false))
+    }
+
+    /// Same as `fee_spend`, returning `BalanceFetcherError::Cancelled` as soon as `cancel` is set
+    ///
+    /// `cancel` is checked before each transaction is fetched, so the call returns within one RPC round trip.
+    pub fn fee_spend_cancellable(
+        &self,
+        wallet_address: &Pubkey,
+        until: Option<Signature>,
+        limit: usize,
+        cancel: &AtomicBool,
+    ) -> Result<FeeSpend> {
+        let signatures = paginate(limit, MAX_GET_CONFIRMED_SIGNATURES_FOR_ADDRESS2_LIMIT, |last, page_limit| {
+            check_cancelled(cancel)?;
+            let config = GetConfirmedSignaturesForAddress2Config {
+                before: last.map(|status| Signature::from_str(&status.signature)).transpose()?,
+                until,
+                limit: Some(page_limit),
+                commitment: Some(self.rpc.commitment()),
+            };
+            Ok(self.rpc.get_signatures_for_address_with_config(wallet_address, config)?)
+        })?;
+        let mut transactions = Vec::new();
+        for status in signatures {
+            check_cancelled(cancel)?;
+            let signature = Signature::from_str(&status.signature)?;
+            let config = RpcTransactionConfig {
+                encoding: Some(UiTransactionEncoding::Base64),
+                commitment: Some(self.rpc.commitment()),
+                max_supported_transaction_version: Some(0),
+            };
+            let tx = self.rpc.get_transaction_with_config(&signature, config)?;
+            let (Some(meta), Some(versioned_tx)) = (tx.transaction.meta, tx.transaction.transaction.decode()) else {
+                log::warn!("transaction {} has no status meta or cannot be decoded", signature);
+                continue;
+            };
+            // The fee payer is always the first account of the message
+            if versioned_tx.message.static_account_keys().first() != Some(wallet_address) {
+                continue;
+            }
+            let base_fee = (LAMPORTS_PER_SIGNATURE * versioned_tx.signatures.len() as u64).min(meta.fee);
+            transactions.push(TransactionFee {
+                signature,
+                block_time: tx.block_time,
+                base_fee,
+                priority_fee: meta.fee - base_fee,
+            });
+        }
+        Ok(FeeSpend { transactions })
+    }
+
+    /// Recommend the minimum SOL a wallet should keep to pay its transaction fees, from its recent fee spend
+    ///
+    /// It is the 95th percentile of the wallet's daily fee spend multiplied by `safety_factor`.
+    ///
+    /// # Arguments
+    /// - `wallet_address` - The wallet address
+    /// - `limit` - Maximum number of recent transactions to look at
+    /// - `safety_factor` - Multiplier on the p95 daily fee spend, e.g. 3.0 to cover three busy days
+    ///
+    /// # Returns
+    /// - `u64` - The recommended minimum SOL balance in lamports, 0 if the wallet paid no fees recently
+    pub fn recommended_sol_reserve(&self, wallet_address: &Pubkey, limit: usize, safety_factor: f64) -> Result<u64> {
+        let fee_spend = self.fee_spend(wallet_address, None, limit)?;
+        let p95 = percentile(fee_spend.daily_fees(), 0.95);
+        Ok((p95 as f64 * safety_factor).ceil() as u64)
+    }
+
+    /// Find the mints for which a wallet has no associated token account yet, e.g. before automated payouts
+    ///
+    /// # Arguments
+    /// - `wallet_address` - The wallet address
+    /// - `token_mint_addresses` - The mint addresses to check, either owned by Token or Token-2022 program
+    ///
+    /// # Returns
+    /// - `Vec<MissingTokenAccount>` - The associated token accounts to create and their rent cost
+    pub fn missing_token_accounts(
+        &self,
+        wallet_address: &Pubkey,
+        token_mint_addresses: &[MintAddress],
+    ) -> Result<Vec<MissingTokenAccount>> {
+        let mint_pubkeys: Vec<Pubkey> = token_mint_addresses.iter().map(MintAddress::pubkey).collect();
+        let (_, mints) = self.get_multiple_accounts_chunked(&mint_pubkeys)?;
+        let mut candidates = Vec::new();
+        for (mint_address, mint) in mint_pubkeys.iter().zip(mints) {
+            let Some(mint) = mint else {
+                return Err(BalanceFetcherError::MissingAccount(*mint_address));
+            };
+            let address = spl_associated_token_account::get_associated_token_address_with_program_id(
+                wallet_address,
+                mint_address,
+                &mint.owner,
+            );
+            // Token-2022 associated token accounts are created with the extensions required by the mint,
+            // plus immutable owner, which makes them larger than classic ones.
+            let account_len = if mint.owner == spl_token_2022::id() {
+                let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint.data)?;
+                let mut extensions = ExtensionType::get_required_init_account_extensions(
+                    &mint_state.get_extension_types()?,
+                );
+                extensions.push(ExtensionType::ImmutableOwner);
+                ExtensionType::try_calculate_account_len::<spl_token_2022::state::Account>(&extensions)?
+            } else {
+                spl_token::state::Account::LEN
+            };
+            candidates.push((*mint_address, address, mint.owner, account_len));
+        }
+
+        let addresses: Vec<Pubkey> = candidates.iter().map(|(_, address, _, _)| *address).collect();
+        let (_, accounts) = self.get_multiple_accounts_chunked(&addresses)?;
+        let mut missing = Vec::new();
+        // Most accounts share a few lengths, their rent is fetched once per length
+        let mut rents: HashMap<usize, u64> = HashMap::new();
+        for ((mint, address, token_program, account_len), account) in candidates.into_iter().zip(accounts) {
+            if account.is_none() {
+                let rent = match rents.get(&account_len) {
+                    Some(rent) => *rent,
+                    None => {
+                        let rent = self.rpc.get_minimum_balance_for_rent_exemption(account_len)?;
+                        rents.insert(account_len, rent);
+                        rent
+                    }
+                };
+                missing.push(MissingTokenAccount { mint, address, token_program, rent });
+            }
+        }
+        Ok(missing)
+    }
+
+    /// Fetch the lamports, owner, data length and rent status of any account
+    ///
+    /// # Arguments
+    /// - `address` - The account address
+    ///
+    /// # Returns
+    /// - `AccountOverview` - Basic information of the account
+    pub fn account_overview(&self, address: &Pubkey) -> Result<AccountOverview> {
+        let account = self.rpc.get_account(address)?;
+        let rent_exempt_minimum = self.rpc.get_minimum_balance_for_rent_exemption(account.data.len())?;
+        Ok(AccountOverview {
+            lamports: account.lamports,
+            owner: account.owner,
+            executable: account.executable,
+            data_len: account.data.len(),
+            rent_exempt_minimum,
+        })
+    }
+
+    /// Classify an address as wallet, token account or other account
+    ///
+    /// # Arguments
+    /// - `address` - The address
+    ///
+    /// # Returns
+    /// - `AddressKind` - The kind of the address, with the balance, mint and owner if it is a token account
+    pub fn classify_address(&self, address: &Pubkey) -> Result<AddressKind> {
+        let account = self.rpc.get_account_with_commitment(address, self.rpc.commitment())?.value;
+        self.classify_account(account)
+    }
+
+    /// Same as `classify_address` for many addresses, fetching their accounts in batches
+    ///
+    /// # Arguments
+    /// - `addresses` - The addresses
+    ///
+    /// # Returns
+    /// - `Vec<AddressKind>` - The kind of each address, in the order of `addresses`
+    pub fn classify_addresses(&self, addresses: &[Pubkey]) -> Result<Vec<AddressKind>> {
+        let (_, accounts) = self.get_multiple_accounts_chunked(addresses)?;
+        accounts.into_iter().map(|account| self.classify_account(account)).collect()
+    }
+
+    fn classify_account(&self, account: Option<Account>) -> Result<AddressKind> {
+        let Some(account) = account else {
+            return Ok(AddressKind::Wallet);
+        };
+        if account.owner == solana_sdk::system_program::id() {
+            return Ok(AddressKind::Wallet);
+        }
+        if account.owner == spl_token::id() || account.owner == spl_token_2022::id() {
+            // Mints and multisigs are owned by token programs as well, but cannot be unpacked as token accounts
+            if let Ok(token_account) = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data) {
+                let mint = token_account.base.mint;
+                let decimals = self.mint_info(&MintAddress::from(mint))?.decimals;
+                return Ok(AddressKind::TokenAccount(TokenAccountInfo {
+                    mint,
+                    owner: token_account.base.owner,
+                    amount: Amount { amount: token_account.base.amount, decimals },
+                    token_program: account.owner,
+                }));
+            }
+        }
+        Ok(AddressKind::Other { owner: account.owner })
+    }
+
+    /// Fetch the largest holders of a SPL token
+    ///
+    /// # Arguments
+    /// - `token_mint_address` - The mint address of the token
+    ///
+    /// # Returns
+    /// - `MintHolders` - The supply of the token and its largest token accounts
+    pub fn mint_top_holders(&self, token_mint_address: &MintAddress) -> Result<MintHolders> {
+        let supply = self.rpc.get_token_supply(token_mint_address)?;
+        let largest_accounts = self.rpc.get_token_largest_accounts(token_mint_address)?;
+        let holders = largest_accounts
+            .into_iter()
+            .map(|acc| {
+                Ok(TokenHolder {
+                    token_account: Pubkey::from_str(&acc.address)?,
+                    amount: u64::from_str(&acc.amount.amount)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(MintHolders {
+            supply: u64::from_str(&supply.amount)?,
+            decimals: supply.decimals,
+            holders,
+        })
+    }
+
+    /// Fetch the supply, decimals and authorities of a SPL token
+    ///
+    /// # Arguments
+    /// - `token_mint_address` - The mint address of the token, either owned by Token or Token-2022 program
+    ///
+    /// # Returns
+    /// - `MintInfo` - The supply, decimals, mint and freeze authorities of the token
+    pub fn mint_info(&self, token_mint_address: &MintAddress) -> Result<MintInfo> {
+        let account = self.get_account_cached(token_mint_address, AccountKind::Mint)?;
+        let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&account.data)?.base;
+        Ok(MintInfo {
+            supply: mint.supply,
+            decimals: mint.decimals,
+            mint_authority: mint.mint_authority.into(),
+            freeze_authority: mint.freeze_authority.into(),
+        })
+    }
+
+    /// Fetch the LP positions of a wallet in all Raydium CLMM pools it has position NFTs of
+    ///
+    /// Amounts are those withdrawable at each pool's current price, per position as by `raydium_pool_positions` and
+    /// summed up per pool as by `raydium_pool_position`.
+    ///
+    /// # Arguments
+    /// - `wallet_address` - The wallet address
+    ///
+    /// # Returns
+    /// - `Vec<PoolPositions>` - The positions grouped by pool, ordered by pool ID, empty if the wallet has none
+    pub fn all_raydium_positions(&self, wallet_address: &Pubkey) -> Result<Vec<PoolPositions>> {
+        let pdas = self.wallet_position_pdas(wallet_address)?;
+        let (_, accounts) = self.get_multiple_accounts_chunked(&pdas)?;
+        let mut pool_positions: BTreeMap<Pubkey, Vec<(Pubkey, raydium_amm_v3::states::PersonalPositionState)>> =
+            BTreeMap::new();
+        for (address, account) in pdas.iter().zip(accounts) {
+            // A position closed since its PDA was listed has no account anymore
+            let account = match account {
+                Some(account) => account,
+                None => continue,
+            };
+            match deserialize_anchor_account::<raydium_amm_v3::states::PersonalPositionState>(&account) {
+                Ok(position) => pool_positions.entry(position.pool_id).or_default().push((*address, position)),
+                Err(err) => log::warn!("skipping position {}: {}", address, err),
+            }
+        }
+
+        let pool_ids: Vec<Pubkey> = pool_positions.keys().copied().collect();
+        let (_, pool_accounts) = self.get_multiple_accounts_chunked(&pool_ids)?;
+        pool_positions
+            .into_iter()
+            .zip(pool_accounts)
+            .map(|((pool_id, positions), pool_account)| {
+                let pool_account = pool_account.ok_or(BalanceFetcherError::MissingAccount(pool_id))?;
+                let pool_state = deserialize_anchor_account::<raydium_amm_v3::states::PoolState>(&pool_account)?;
+                // PoolState is packed, copy fields out before use
+                let (mint_0, decimals_0) = (pool_state.token_mint_0, pool_state.mint_decimals_0);
+                let (mint_1, decimals_1) = (pool_state.token_mint_1, pool_state.mint_decimals_1);
+                let positions = raydium_positions(&positions, &pool_state)?;
+                let (amount_0, amount_1) = total_position_amounts(&positions)?;
+                Ok(PoolPositions {
+                    pool_id,
+                    mint_0,
+                    mint_1,
+                    positions,
+                    amount_0: Amount { amount: amount_0, decimals: decimals_0 },
+                    amount_1: Amount { amount: amount_1, decimals: decimals_1 },
+                })
+            })
+            .collect()
+    }
+
+    /// Fetch the LP position amounts of Raydium SOL-USDC.1bp pool
+    ///
+    /// # Arguments
+    /// - `wallet_address` - The wallet address
+    ///
+    /// # Returns
+    /// - `(u64, u64)` - The total amount of SOL and USDC of the wallet_address's LP positions in the given pool
+    pub fn position_sol_usdc_1bp(&self, wallet_address: &Pubkey) -> Result<(u64, u64)> {
+        self.raydium_pool_position(wallet_address, &PoolId::sol_usdc_1bp())
+    }
+
+    /// Fetch LP position amounts of Raydium pool
+    ///
+    /// The amounts are those withdrawable at the pool's current price, the total of `raydium_pool_positions`.
+    ///
+    /// # Arguments
+    /// - `wallet_address` - The wallet address
+    /// - `pool_id` - The pool ID, e.g. 8sLbNZoA1cfnvMJLPfp98ZLAnFSYCFApfJKMbiXNLwxj (SOL-USDC.1bp Pool in Raydium mainnet)
+    ///
+    /// # Returns
+    /// - `(u64, u64)` - The total amount of token 0 and token 1 of the wallet_address's LP positions in the given pool
+    pub fn raydium_pool_position(&self, wallet_address: &Pubkey, pool_id: &PoolId) -> Result<(u64, u64)> {
+        let positions = self.wallet_position_pdas(wallet_address)?;
+        self.raydium_pool_position_by_pdas(&positions, pool_id)
+    }
+
+    /// Fetch the LP positions of a wallet in a Raydium pool one by one, see `raydium_pool_position` for their total
+    ///
+    /// # Arguments
+    /// - `wallet_address` - The wallet address
+    /// - `pool_id` - The pool ID
+    ///
+    /// # Returns
+    /// - `Vec<RaydiumPosition>` - The wallet_address's positions in the pool, which `total_position_amounts` sums up
+    pub fn raydium_pool_positions(&self, wallet_address: &Pubkey, pool_id: &PoolId) -> Result<Vec<RaydiumPosition>> {
+        let pdas = self.wallet_position_pdas(wallet_address)?;
+        self.raydium_pool_positions_by_pdas(&pdas, pool_id)
+    }
+
+    /// Fetch LP position amounts of Raydium pool by position NFT mints, regardless of who holds the NFTs
+    ///
+    /// Useful for positions custodied in PDAs or other accounts that are not the wallet being monitored.
+    ///
+    /// # Arguments
+    /// - `nft_mints` - The mint addresses of the position NFTs
+    /// - `pool_id` - The pool ID, positions of other pools are ignored
+    ///
+    /// # Returns
+    /// - `(u64, u64)` - The total amount of token 0 and token 1 of the given positions in the given pool
+    pub fn raydium_pool_position_by_nft_mints(&self, nft_mints: &[Pubkey], pool_id: &PoolId) -> Result<(u64, u64)> {
+        let raydium_v3_program = Pubkey::from_str(RAYDIUM_V3_PROGRAM_ID).unwrap();
+        let positions: Vec<Pubkey> = nft_mints
+            .iter()
+            .map(|mint| get_position_pda(mint, &raydium_v3_program))
+            .collect();
+        self.raydium_pool_position_by_pdas(&positions, pool_id)
+    }
+
+    /// Value the LP positions of Raydium SOL-USDC.1bp pool as if withdrawn and swapped to USDC now
+    ///
+    /// # Arguments
+    /// - `wallet_address` - The wallet address
+    ///
+    /// # Returns
+    /// - `Vec<PositionExitValue>` - The USDC exit value of each of the wallet_address's positions in the pool
+    pub fn position_exit_values_sol_usdc_1bp(&self, wallet_address: &Pubkey) -> Result<Vec<PositionExitValue>> {
+        self.raydium_position_exit_values(wallet_address, &PoolId::sol_usdc_1bp(), &MintAddress::usdc())
+    }
+
+    /// Value LP positions of Raydium pool as if fully withdrawn and swapped into one token of the pool now
+    ///
+    /// This is the realistic liquidation value: the other token is swapped in the same pool, so the price
+    /// impact is taken into account rather than valuing at the mid price. Each position is quoted on its own
+    /// against the current pool state, as if it were the only one being exited.
+    ///
+    /// # Arguments
+    /// - `wallet_address` - The wallet address
+    /// - `pool_id` - The pool ID
+    /// - `exit_mint` - The mint of the token to exit into, either token 0 or token 1 of the pool
+    ///
+    /// # Returns
+    /// - `Vec<PositionExitValue>` - The exit value of each of the wallet_address's positions in the pool
+    pub fn raydium_position_exit_values(
+        &self,
+        wallet_address: &Pubkey,
+        pool_id: &PoolId,
+        exit_mint: &MintAddress,
+    ) -> Result<Vec<PositionExitValue>> {
+        self.position_exit_values_with(wallet_address, pool_id, exit_mint, |_, amount_in, direction| {
+            let quote = self.quote_swap(pool_id, amount_in, direction)?;
+            Ok((quote.amount_out, quote.price_impact))
+        })
+    }
+
+    /// Value LP positions of Raydium pool as if fully withdrawn and swapped into one token through Jupiter
+    ///
+    /// Same as `raydium_position_exit_values`, but the other token is swapped through the routes aggregated by
+    /// Jupiter instead of the position's own pool, which matters for tokens whose best liquidity is elsewhere.
+    ///
+    /// # Arguments
+    /// - `jupiter` - The Jupiter quote API client
+    /// - `wallet_address` - The wallet address
+    /// - `pool_id` - The pool ID
+    /// - `exit_mint` - The mint of the token to exit into, either token 0 or token 1 of the pool
+    ///
+    /// # Returns
+    /// - `Vec<PositionExitValue>` - The exit value of each of the wallet_address's positions in the pool
+    #[cfg(feature = "jupiter")]
+    pub fn raydium_position_exit_values_jupiter(
+        &self,
+        jupiter: &JupiterClient,
+        wallet_address: &Pubkey,
+        pool_id: &PoolId,
+        exit_mint: &MintAddress,
+    ) -> Result<Vec<PositionExitValue>> {
+        self.position_exit_values_with(wallet_address, pool_id, exit_mint, |input_mint, amount_in, _| {
+            let quote = jupiter.quote(input_mint, exit_mint, amount_in)?;
+            Ok((quote.out_amount, quote.price_impact))
+        })
+    }
+
+    /// Compute exit values of positions, with `quote` returning the output amount and price impact of swapping
+    /// an amount of the given input mint into the exit mint
+    fn position_exit_values_with<F>(
+        &self,
+        wallet_address: &Pubkey,
+        pool_id: &Pubkey,
+        exit_mint: &Pubkey,
+        quote: F,
+    ) -> Result<Vec<PositionExitValue>>
+    where
+        F: Fn(&Pubkey, u64, SwapDirection) -> Result<(u64, f64)>,
+    {
+        let pool_account = self.get_account_cached(pool_id, AccountKind::Pool)?;
+        let pool_state = deserialize_anchor_account::<raydium_amm_v3::states::PoolState>(&pool_account)?;
+        let (token_mint_0, token_mint_1) = (pool_state.token_mint_0, pool_state.token_mint_1);
+        let (direction, input_mint) = if *exit_mint == token_mint_1 {
+            (SwapDirection::ZeroForOne, token_mint_0)
+        } else if *exit_mint == token_mint_0 {
+            (SwapDirection::OneForZero, token_mint_1)
+        } else {
+            return Err(BalanceFetcherError::InvalidInput(format!(
+                "mint {} is not a token of pool {}",
+                exit_mint, pool_id
+            )));
+        };
+
+        let positions = self.wallet_position_pdas(wallet_address)?;
+        let positions = self.fetch_pool_positions(&positions, pool_id)?;
+        positions
+            .iter()
+            .map(|(address, position)| {
+                let math::PositionAmounts { amount_0, amount_1, .. } = math::position_amounts(position, &pool_state)?;
+                let (amount_to_swap, amount_kept) = match direction {
+                    SwapDirection::ZeroForOne => (amount_0, amount_1),
+                    SwapDirection::OneForZero => (amount_1, amount_0),
+                };
+                let (exit_amount, price_impact) = if amount_to_swap == 0 {
+                    (amount_kept, 0.0)
+                } else {
+                    let (amount_out, price_impact) = quote(&input_mint, amount_to_swap, direction)?;
+                    let exit_amount = amount_kept.checked_add(amount_out).ok_or_else(|| {
+                        BalanceFetcherError::Math(format!("exit amount of position {} overflows u64", address))
+                    })?;
+                    (exit_amount, price_impact)
+                };
+                Ok(PositionExitValue { position: *address, amount_0, amount_1, exit_amount, price_impact })
+            })
+            .collect()
+    }
+
+    pub(crate) fn wallet_position_pdas(&self, wallet_address: &Pubkey) -> Result<Vec<Pubkey>> {
+        if let Some(positions) = self.cache.as_ref().and_then(|cache| cache.position_pdas(wallet_address)) {
+            return Ok(positions);
+        }
+        let raydium_v3_program = Pubkey::from_str(RAYDIUM_V3_PROGRAM_ID).unwrap();
+        let positions = self.get_nft_account_and_position_by_owner(
+            &wallet_address,
+            spl_token_2022::id(),
+            &raydium_v3_program,
+        )?;
+        let positions: Vec<Pubkey> = positions
+            .iter()
+            .map(|item| item.position)
+            .collect();
+        if let Some(cache) = &self.cache {
+            cache.insert_position_pdas(*wallet_address, positions.clone());
+        }
+        Ok(positions)
+    }
+
+    /// Fetch an account, from the cache if it is enabled and has the account fresh for its kind
+    pub(crate) fn get_account_cached(&self, address: &Pubkey, kind: AccountKind) -> Result<Account> {
+        if let Some(account) = self.cache.as_ref().and_then(|cache| cache.account(address, kind)) {
+            return Ok(account);
+        }
+        let account = self.rpc.get_account(address)?;
+        if let Some(cache) = &self.cache {
+            cache.insert_account(*address, account.clone());
+        }
+        Ok(account)
+    }
+
+    /// Fetch the position states among `positions` PDAs that belong to the pool, along with their PDAs
+    pub(crate) fn fetch_pool_positions(
+        &self,
+        positions: &[Pubkey],
+        pool_id: &Pubkey,
+    ) -> Result<Vec<(Pubkey, raydium_amm_v3::states::PersonalPositionState)>> {
+        let (_, accounts) = self.get_multiple_accounts_chunked(positions)?;
+        Ok(pool_positions_from_accounts(positions, accounts, pool_id))
+    }
+
+    /// Fetch any number of accounts, in as few `getMultipleAccounts` requests as the RPC limit allows
+    ///
+    /// # Returns
+    /// - `(u64, Vec<Option<Account>>)` - The slot of the first request, and the accounts in the order of `addresses`
+    pub(crate) fn get_multiple_accounts_chunked(&self, addresses: &[Pubkey]) -> Result<(u64, Vec<Option<Account>>)> {
+        let mut slot = 0;
+        let mut accounts = Vec::with_capacity(addresses.len());
+        for (i, chunk) in addresses.chunks(MAX_MULTIPLE_ACCOUNTS).enumerate() {
+            let rsp = self.rpc.get_multiple_accounts_with_commitment(chunk, self.rpc.commitment())?;
+            if i == 0 {
+                slot = rsp.context.slot;
+            }
+            accounts.extend(rsp.value);
+        }
+        Ok((slot, accounts))
+    }
+
+    fn raydium_pool_position_by_pdas(&self, pdas: &[Pubkey], pool_id: &PoolId) -> Result<(u64, u64)> {
+        total_position_amounts(&self.raydium_pool_positions_by_pdas(pdas, pool_id)?)
+    }
+
+    fn raydium_pool_positions_by_pdas(&self, pdas: &[Pubkey], pool_id: &PoolId) -> Result<Vec<RaydiumPosition>> {
+        let positions = self.fetch_pool_positions(pdas, pool_id)?;
+        let pool_account = self.get_account_cached(pool_id, AccountKind::Pool)?;
+        let pool_state = deserialize_anchor_account::<raydium_amm_v3::states::PoolState>(&pool_account)?;
+        raydium_positions(&positions, &pool_state)
+    }
+
+    /// Fetch the unclaimed trading fees of the LP positions of a wallet in a Raydium pool
+    ///
+    /// They are not part of the principal returned by `raydium_pool_position`. Fees earned since a position was last
+    /// updated on chain are included, computed from the fee growth inside its range like the Raydium client does.
+    ///
+    /// # Arguments
+    /// - `wallet_address` - The wallet address
+    /// - `pool_id` - The pool ID
+    ///
+    /// # Returns
+    /// - `(u64, u64)` - The total unclaimed fees of token 0 and token 1 of the wallet_address's positions in the pool
+    pub fn raydium_pool_position_fees(&self, wallet_address: &Pubkey, pool_id: &PoolId) -> Result<(u64, u64)> {
+        let pdas = self.wallet_position_pdas(wallet_address)?;
+        let positions = self.fetch_pool_positions(&pdas, pool_id)?;
+        let pool_account = self.get_account_cached(pool_id, AccountKind::Pool)?;
+        let pool_state = deserialize_anchor_account::<raydium_amm_v3::states::PoolState>(&pool_account)?;
+        let tick_states = self.positions_tick_states(pool_id, &pool_state, &positions)?;
+        let overflow = || BalanceFetcherError::Math("unclaimed fees of positions overflow u64".to_string());
+        let mut totals: (u64, u64) = (0, 0);
+        for ((_, position), (tick_lower, tick_upper)) in positions.iter().zip(&tick_states) {
+            let (fee_0, fee_1) = math::unclaimed_fees(position, &pool_state, tick_lower, tick_upper)?;
+            totals = (
+                totals.0.checked_add(fee_0).ok_or_else(overflow)?,
+                totals.1.checked_add(fee_1).ok_or_else(overflow)?,
+            );
+        }
+        Ok(totals)
+    }
+
+    /// Fetch the pending reward emissions of the LP positions of a wallet in a Raydium pool
+    ///
+    /// A pool has up to three reward tokens. Rewards emitted since the pool was last updated on chain are included,
+    /// as of now.
+    ///
+    /// # Arguments
+    /// - `wallet_address` - The wallet address
+    /// - `pool_id` - The pool ID
+    ///
+    /// # Returns
+    /// - `Vec<PendingReward>` - The reward mints of the pool with the total pending amount of the wallet_address's
+    ///   positions, in the pool's order
+    pub fn raydium_pool_position_rewards(
+        &self,
+        wallet_address: &Pubkey,
+        pool_id: &PoolId,
+    ) -> Result<Vec<PendingReward>> {
+        let pdas = self.wallet_position_pdas(wallet_address)?;
+        let positions = self.fetch_pool_positions(&pdas, pool_id)?;
+        let pool_account = self.get_account_cached(pool_id, AccountKind::Pool)?;
+        let pool_state = deserialize_anchor_account::<raydium_amm_v3::states::PoolState>(&pool_account)?;
+        let tick_states = self.positions_tick_states(pool_id, &pool_state, &positions)?;
+        let timestamp = unix_timestamp();
+        let reward_infos = pool_state.reward_infos;
+        let mut totals: Vec<PendingReward> = reward_infos
+            .iter()
+            .filter(|reward_info| reward_info.token_mint != Pubkey::default())
+            .map(|reward_info| PendingReward { mint: reward_info.token_mint, amount: 0 })
+            .collect();
+        for ((_, position), (tick_lower, tick_upper)) in positions.iter().zip(&tick_states) {
+            let rewards = math::pending_rewards(position, &pool_state, tick_lower, tick_upper, timestamp)?;
+            for (total, reward) in totals.iter_mut().zip(rewards) {
+                total.amount = total.amount.checked_add(reward.amount).ok_or_else(|| {
+                    BalanceFetcherError::Math(format!("pending rewards of {} overflow u64", reward.mint))
+                })?;
+            }
+        }
+        Ok(totals)
+    }
+
+    /// Fetch the states of the lower and upper ticks of positions in a pool, with the tick arrays of all their range
+    /// bounds at once
+    ///
+    /// # Returns
+    /// - `Vec<(TickState, TickState)>` - The lower and upper tick states, in the order of `positions`
+    pub(crate) fn positions_tick_states(
+        &self,
+        pool_id: &Pubkey,
+        pool_state: &raydium_amm_v3::states::PoolState,
+        positions: &[(Pubkey, raydium_amm_v3::states::PersonalPositionState)],
+    ) -> Result<Vec<(raydium_amm_v3::states::TickState, raydium_amm_v3::states::TickState)>> {
+        let raydium_v3_program = Pubkey::from_str(RAYDIUM_V3_PROGRAM_ID).unwrap();
+        let tick_spacing = pool_state.tick_spacing as i32;
+        let tick_array_span = tick_spacing * raydium_amm_v3::states::TICK_ARRAY_SIZE;
+        let start_index = |tick: i32| tick.div_euclid(tick_array_span) * tick_array_span;
+        let start_indexes: Vec<i32> = positions
+            .iter()
+            .flat_map(|(_, position)| [start_index(position.tick_lower_index), start_index(position.tick_upper_index)])
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let tick_array_addresses: Vec<Pubkey> = start_indexes
+            .iter()
+            .map(|start| get_tick_array_pda(pool_id, *start, &raydium_v3_program))
+            .collect();
+        let (_, accounts) = self.get_multiple_accounts_chunked(&tick_array_addresses)?;
+        let mut tick_arrays = HashMap::new();
+        for (start, account) in start_indexes.into_iter().zip(accounts) {
+            if let Some(account) = account {
+                let tick_array = deserialize_anchor_account::<raydium_amm_v3::states::TickArrayState>(&account)?;
+                tick_arrays.insert(start, tick_array.ticks);
+            }
+        }
+
+        // Only positions without liquidity can be bounded by a tick array that does not exist, and they have earned
+        // nothing since their last update
+        let tick_state = |tick: i32| {
+            let offset = ((tick - start_index(tick)) / tick_spacing) as usize;
+            tick_arrays.get(&start_index(tick)).map(|ticks| ticks[offset]).unwrap_or_default()
+        };
+        Ok(positions
+            .iter()
+            .map(|(_, position)| (tick_state(position.tick_lower_index), tick_state(position.tick_upper_index)))
+            .collect())
+    }
+
+    /// Fetch the current price of a Raydium pool from its square root price
+    ///
+    /// # Arguments
+    /// - `pool_id` - The pool ID
+    ///
+    /// # Returns
+    /// - `PoolPrice` - The price of token 0 in token 1 and the inverse, along with the raw pool price
+    pub fn pool_price(&self, pool_id: &PoolId) -> Result<PoolPrice> {
+        let pool_account = self.get_account_cached(pool_id, AccountKind::Pool)?;
+        let pool_state = deserialize_anchor_account::<raydium_amm_v3::states::PoolState>(&pool_account)?;
+        let (decimals_0, decimals_1) = (pool_state.mint_decimals_0, pool_state.mint_decimals_1);
+        let price_0_in_1 = math::sqrt_price_x64_to_price(pool_state.sqrt_price_x64, decimals_0, decimals_1);
+        Ok(PoolPrice {
+            pool_id: pool_id.pubkey(),
+            mint_0: pool_state.token_mint_0,
+            mint_1: pool_state.token_mint_1,
+            decimals_0,
+            decimals_1,
+            sqrt_price_x64: pool_state.sqrt_price_x64,
+            tick_current: pool_state.tick_current,
+            price_0_in_1,
+            price_1_in_0: 1.0 / price_0_in_1,
+        })
+    }
+
+    /// Fetch aggregate statistics of all positions in a Raydium pool
+    ///
+    /// It scans all position accounts of the pool with `getProgramAccounts`, which some RPC providers
+    /// (including the public mainnet endpoint) refuse for the Raydium CLMM program.
     ///
     /// # Arguments
-    /// - `wallet_address` - The wallet address
+    /// - `pool_id` - The pool ID
     ///
     /// # Returns
-    /// - `u64` - The WSOL balance of the wallet
-    pub fn balance_wsol(&self, wallet_address: &Pubkey) -> Result<u64> {
-        let wsol_mint_address = Pubkey::from_str(WSOL_MINT_ADDRESS).unwrap();
-        let balance = self.balance_spl_token(wallet_address, &wsol_mint_address)?;
-        Ok(balance.amount)
+    /// - `PoolStats` - Position count, liquidity and fee growth of the pool
+    pub fn raydium_pool_stats(&self, pool_id: &PoolId) -> Result<PoolStats> {
+        self.raydium_pool_stats_with_progress(pool_id, |_| {})
     }
 
-    /// Fetch the SOL and WSOL (Wrapped SOL) balance sum of a wallet
+    /// Same as `raydium_pool_stats`, calling `progress` after each of the scan's requests
     ///
     /// # Arguments
-    /// - `wallet_address` - The wallet address
+    /// - `pool_id` - The pool ID
+    /// - `progress` - Called with the progress of the scan over the pool's positions
     ///
     /// # Returns
-    /// - `u64` - The SOL and WSOL balance of the given wallet
-    pub fn balance_sol_unified(&self, wallet_address: &Pubkey) -> Result<u64> {
-        let sol_balance = self.balance_sol(wallet_address)?;
-        let wsol_balance = self.balance_wsol(wallet_address)?;
-        Ok(sol_balance + wsol_balance)
+    /// - `PoolStats` - Position count, liquidity and fee growth of the pool
+    pub fn raydium_pool_stats_with_progress<P: FnMut(&ScanProgress)>(
+        &self,
+        pool_id: &PoolId,
+        progress: P,
+    ) -> Result<PoolStats> {
+        self.raydium_pool_stats_cancellable(pool_id, progress, &AtomicBool::new(false))
     }
 
-    /// Fetch the balance of a SPL token account
+    /// Same as `raydium_pool_stats_with_progress`, returning `BalanceFetcherError::Cancelled` once `cancel` is set
+    ///
+    /// `cancel` is checked before each of the scan's requests, e.g. set it when the client waiting for the
+    /// result disconnects.
     ///
     /// # Arguments
-    /// - `wallet_address` - The wallet address
-    /// - `token_mint_address` - The mint address of the token
+    /// - `pool_id` - The pool ID
+    /// - `progress` - Called with the progress of the scan over the pool's positions
+    /// - `cancel` - Set from another thread to abort the scan
     ///
     /// # Returns
-    /// - `SPLToken` - The balance and decimals of the token account
-    pub fn balance_spl_token(&self, wallet_address: &Pubkey, token_mint_address: &Pubkey) -> Result<SPLToken> {
-        let addr = spl_associated_token_account::get_associated_token_address(&wallet_address, &token_mint_address);
-        let ui_token_amount =
-            match self.rpc.get_token_account_balance(&addr) {
-                Ok(ui_token_amount) => ui_token_amount,
-                Err(err) => {
-                    match err.kind {
-                        ErrorKind::RpcError(RpcError::RpcResponseError { .. }) => {
-                            // If the token account does not exist, RPC return error.
-                            // This is a temporary solution.
-                            log::warn!("address {} does not have token account for SPL token {}", addr, token_mint_address);
-                            return Ok(SPLToken { amount: 0, decimals: 0 });
-                        }
-                        _ => {
-                            return Err(err.into());
-                        }
-                    }
+    /// - `PoolStats` - Position count, liquidity and fee growth of the pool
+    pub fn raydium_pool_stats_cancellable<P: FnMut(&ScanProgress)>(
+        &self,
+        pool_id: &PoolId,
+        progress: P,
+        cancel: &AtomicBool,
+    ) -> Result<PoolStats> {
+        check_cancelled(cancel)?;
+        let pool_account = self.get_account_cached(pool_id, AccountKind::Pool)?;
+        let pool_state = deserialize_anchor_account::<raydium_amm_v3::states::PoolState>(&pool_account)?;
+
+        let tick_current = pool_state.tick_current;
+        let mut stats = PoolStats {
+            positions: 0,
+            open_positions: 0,
+            total_liquidity: 0,
+            in_range_liquidity: 0,
+            tick_current,
+            fee_growth_global_0_x64: pool_state.fee_growth_global_0_x64,
+            fee_growth_global_1_x64: pool_state.fee_growth_global_1_x64,
+        };
+        self.for_each_pool_position(pool_id, progress, cancel, |position| {
+            stats.positions += 1;
+            if position.liquidity == 0 {
+                return;
+            }
+            stats.open_positions += 1;
+            stats.total_liquidity += position.liquidity;
+            if position.tick_lower_index <= tick_current && tick_current < position.tick_upper_index {
+                stats.in_range_liquidity += position.liquidity;
+            }
+        })?;
+        Ok(stats)
+    }
+
+    /// Scan the tick range and liquidity of all positions in a pool with bounded memory
+    ///
+    /// Positions are fetched by `getProgramAccounts` with only the bytes needed sliced out of each account, in a
+    /// single request first. A request rejected by the RPC node, e.g. for a response too large for pools with
+    /// hundreds of thousands of positions, is split into 256 requests partitioned by the next byte of the NFT
+    /// mint, down to `MAX_SCAN_PREFIX_LEN` bytes.
+    fn for_each_pool_position<P, F>(
+        &self,
+        pool_id: &Pubkey,
+        mut progress: P,
+        cancel: &AtomicBool,
+        mut f: F,
+    ) -> Result<()>
+    where
+        P: FnMut(&ScanProgress),
+        F: FnMut(PositionRange),
+    {
+        let raydium_v3_program = Pubkey::from_str(RAYDIUM_V3_PROGRAM_ID).unwrap();
+        let started_at = Instant::now();
+        // NFT mint prefixes left to fetch, popped from the end
+        let mut prefixes: Vec<Vec<u8>> = vec![vec![]];
+        let mut steps_done = 0;
+        let mut accounts_fetched = 0;
+        // Share of all positions fetched so far, a prefix of n bytes covers 1/256^n of them
+        let mut fetched_share = 0.0;
+        while let Some(prefix) = prefixes.pop() {
+            check_cancelled(cancel)?;
+            let config = self.position_range_scan_config(pool_id, &prefix);
+            let accounts = match self.rpc.get_program_accounts_with_config(&raydium_v3_program, config) {
+                Ok(accounts) => accounts,
+                Err(err) if prefix.len() < MAX_SCAN_PREFIX_LEN => {
+                    log::debug!("scanning positions with NFT mint prefix {:?} failed, splitting it: {}", prefix, err);
+                    prefixes.extend(split_prefix(&prefix).rev());
+                    continue;
                 }
+                Err(err) => return Err(err.into()),
             };
-        // Amount is the raw balance without decimals, a string representation of u64
-        let amount = u64::from_str(&ui_token_amount.amount).unwrap();
-        let decimals = ui_token_amount.decimals;
-        let spl_token = SPLToken { amount, decimals };
-        Ok(spl_token)
+            for (_, account) in &accounts {
+                f(PositionRange::from_slice(&account.data)?);
+            }
+
+            steps_done += 1;
+            accounts_fetched += accounts.len();
+            fetched_share += 256f64.powi(-(prefix.len() as i32));
+            let eta = started_at.elapsed().mul_f64((1.0 - fetched_share).max(0.0) / fetched_share);
+            progress(&ScanProgress { steps_done, steps_total: steps_done + prefixes.len(), accounts_fetched, eta });
+        }
+        Ok(())
     }
 
-    /// Fetch the LP position amounts of Raydium SOL-USDC.1bp pool
+    /// `getProgramAccounts` config of the tick range and liquidity of the positions in a pool whose NFT mint starts
+    /// with `nft_mint_prefix`, all of them if it is empty
+    fn position_range_scan_config(&self, pool_id: &Pubkey, nft_mint_prefix: &[u8]) -> RpcProgramAccountsConfig {
+        let (offset, length) = POSITION_RANGE_SLICE;
+        let mut filters = vec![
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                0,
+                &raydium_amm_v3::states::PersonalPositionState::DISCRIMINATOR,
+            )),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(POOL_ID_OFFSET, pool_id.as_ref())),
+        ];
+        if !nft_mint_prefix.is_empty() {
+            filters.push(RpcFilterType::Memcmp(Memcmp::new_base58_encoded(NFT_MINT_OFFSET, nft_mint_prefix)));
+        }
+        RpcProgramAccountsConfig {
+            filters: Some(filters),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(self.account_encoding.into()),
+                data_slice: Some(UiDataSliceConfig { offset, length }),
+                commitment: Some(self.rpc.commitment()),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        }
+    }
+
+    /// Check that the RPC endpoint serves the methods the crate relies on, timing each request
     ///
-    /// # Arguments
-    /// - `wallet_address` - The wallet address
+    /// A failed check does not skip the ones after it, so a single call reports all that works and all that does
+    /// not. Requests go through the retry policy of the fetcher, so latencies include retries.
     ///
     /// # Returns
-    /// - `(u64, u64)` - The total amount of SOL and USDC of the wallet_address's LP positions in the given pool
-    pub fn position_sol_usdc_1bp(&self, wallet_address: &Pubkey) -> Result<(u64, u64)> {
-        let pool_id = Pubkey::from_str(SOL_USDC_1BP_POOL_ID)?;
-        self.raydium_pool_position(wallet_address, &pool_id)
+    /// - `Vec<RpcCheck>` - The outcome of each check, in the order they ran
+    pub fn check_rpc(&self) -> Vec<RpcCheck> {
+        let usdc = MintAddress::usdc().pubkey();
+        let pool_id = PoolId::sol_usdc_1bp().pubkey();
+        vec![
+            RpcCheck::run("getHealth", "whether the node is caught up", || {
+                self.rpc.get_health()?;
+                Ok("ok".to_string())
+            }),
+            RpcCheck::run("getVersion", "the node version", || Ok(self.rpc.get_version()?.solana_core)),
+            RpcCheck::run("getMultipleAccounts", "balances, pools and positions", || {
+                let (slot, accounts) = self.get_multiple_accounts_chunked(&[usdc, pool_id])?;
+                let found = accounts.iter().flatten().count();
+                Ok(format!("{} of {} accounts at slot {}", found, accounts.len(), slot))
+            }),
+            RpcCheck::run("getAccountInfo (base64+zstd)", "AccountEncoding::Base64Zstd", || {
+                let config = RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64Zstd),
+                    commitment: Some(self.rpc.commitment()),
+                    ..RpcAccountInfoConfig::default()
+                };
+                let account = self.rpc.get_account_with_config(&usdc, config)?.value;
+                let account = account.ok_or(BalanceFetcherError::MissingAccount(usdc))?;
+                Ok(format!("{} bytes", account.data.len()))
+            }),
+            RpcCheck::run("getProgramAccounts (memcmp filters)", "pool stats", || {
+                // Positions whose NFT mint starts with 2 given bytes are few, which keeps the response small
+                let config = self.position_range_scan_config(&pool_id, &[0, 0]);
+                let raydium_v3_program = Pubkey::from_str(RAYDIUM_V3_PROGRAM_ID).unwrap();
+                let accounts = self.rpc.get_program_accounts_with_config(&raydium_v3_program, config)?;
+                Ok(format!("{} positions", accounts.len()))
+            }),
+        ]
     }
 
-    /// Fetch LP position amounts of Raydium pool
+    /// Quote an exact input swap in a Raydium pool with the current pool state and tick arrays
+    ///
+    /// It is read-only and does not send any transaction. Only tick arrays close to the current price are
+    /// fetched, so a swap large enough to move the price beyond them returns an error.
     ///
     /// # Arguments
-    /// - `wallet_address` - The wallet address
-    /// - `pool_id` - The pool ID, e.g. 8sLbNZoA1cfnvMJLPfp98ZLAnFSYCFApfJKMbiXNLwxj (SOL-USDC.1bp Pool in Raydium mainnet)
+    /// - `pool_id` - The pool ID
+    /// - `amount_in` - Amount of input token in raw units, including fee
+    /// - `direction` - Swap token 0 for token 1, or the opposite
     ///
     /// # Returns
-    /// - `(u64, u64)` - The total amount of token 0 and token 1 of the wallet_address's LP positions in the given pool
-    pub fn raydium_pool_position(&self, wallet_address: &Pubkey, pool_id: &Pubkey) -> Result<(u64, u64)> {
+    /// - `SwapQuote` - The output amount, fee and price impact of the swap
+    pub fn quote_swap(&self, pool_id: &PoolId, amount_in: u64, direction: SwapDirection) -> Result<SwapQuote> {
         let raydium_v3_program = Pubkey::from_str(RAYDIUM_V3_PROGRAM_ID).unwrap();
-        let positions = self.get_nft_account_and_position_by_owner(
-            &wallet_address,
-            spl_token_2022::id(),
-            &raydium_v3_program,
-        );
-        let positions: Vec<Pubkey> = positions
+        let pool_account = self.get_account_cached(pool_id, AccountKind::Pool)?;
+        let pool_state = deserialize_anchor_account::<raydium_amm_v3::states::PoolState>(&pool_account)?;
+        // PoolState is packed, copy fields out before borrowing them
+        let amm_config_address = pool_state.amm_config;
+        let amm_config_account = self.get_account_cached(&amm_config_address, AccountKind::AmmConfig)?;
+        let amm_config = deserialize_anchor_account::<raydium_amm_v3::states::AmmConfig>(&amm_config_account)?;
+
+        let tick_current = pool_state.tick_current;
+        let tick_array_span = pool_state.tick_spacing as i32 * raydium_amm_v3::states::TICK_ARRAY_SIZE;
+        let current_start_index = tick_current.div_euclid(tick_array_span) * tick_array_span;
+        let start_indexes: Vec<i32> = (0..SWAP_QUOTE_TICK_ARRAYS)
+            .map(|i| match direction {
+                SwapDirection::ZeroForOne => current_start_index - i * tick_array_span,
+                SwapDirection::OneForZero => current_start_index + i * tick_array_span,
+            })
+            .collect();
+        let tick_array_addresses: Vec<Pubkey> = start_indexes
             .iter()
-            .map(|item| item.position)
+            .map(|start_index| get_tick_array_pda(pool_id, *start_index, &raydium_v3_program))
             .collect();
-        let positions = self.rpc.get_multiple_accounts(&positions)?;
-
-        let positions = positions.into_iter().filter_map(|p|
-            match p {
-                None => None,
-                Some(rsp) => {
-                    let position = deserialize_anchor_account::<
-                        raydium_amm_v3::states::PersonalPositionState,
-                    >(&rsp);
-                    match position {
-                        Err(_) => {
-                            log::warn!("deserialize_anchor_account error");
-                            None
-                        }
-                        Ok(position) => {
-                            if position.pool_id == *pool_id {
-                                Some(position)
-                            } else {
-                                None
-                            }
-                        }
-                    }
+
+        // A tick array that does not exist has no initialized ticks, the price moves through it freely.
+        let mut ticks = Vec::new();
+        for account in self.rpc.get_multiple_accounts(&tick_array_addresses)?.into_iter().flatten() {
+            let tick_array = deserialize_anchor_account::<raydium_amm_v3::states::TickArrayState>(&account)?;
+            let tick_states = tick_array.ticks;
+            for tick_state in tick_states.iter() {
+                let liquidity_gross = tick_state.liquidity_gross;
+                if liquidity_gross != 0 {
+                    ticks.push(InitializedTick { tick: tick_state.tick, liquidity_net: tick_state.liquidity_net });
                 }
             }
-        ).collect::<Vec<_>>();
-
-        let mut amount_0 = 0;
-        let mut amount_1 = 0;
-        for position in positions {
-            let tick_lower_price_x64 = tick_math::get_sqrt_price_at_tick(position.tick_lower_index)?;
-            let tick_upper_price_x64 = tick_math::get_sqrt_price_at_tick(position.tick_upper_index)?;
-            let delta_amount0 =
-                get_delta_amount_0_unsigned(tick_lower_price_x64, tick_upper_price_x64, position.liquidity, true)?;
-            let delta_amount1 =
-                get_delta_amount_1_unsigned(tick_upper_price_x64, tick_lower_price_x64, position.liquidity, true)?;
-            amount_0 += delta_amount0;
-            amount_1 += delta_amount1;
-        };
-        Ok((amount_0, amount_1))
+        }
+        ticks.sort_by_key(|t| t.tick);
+        let lowest_start_index = *start_indexes.iter().min().unwrap();
+        let highest_start_index = *start_indexes.iter().max().unwrap();
+        let tick_range = (lowest_start_index, highest_start_index + tick_array_span);
+
+        swap_quote::quote_exact_input(
+            pool_state.sqrt_price_x64,
+            tick_current,
+            pool_state.liquidity,
+            amm_config.trade_fee_rate,
+            &ticks,
+            tick_range,
+            amount_in,
+            direction,
+        )
     }
 
+    /// Find Raydium position NFTs held by any owner and derive their position PDAs
+    ///
+    /// The owner does not have to be a wallet: a protocol vault PDA or any other program-owned account works
+    /// as well. Token accounts are fetched base64 encoded and unpacked locally, so it does not depend on the
+    /// RPC provider supporting `jsonParsed`.
+    ///
+    /// # Arguments
+    /// - `owner` - The owner of the token accounts holding position NFTs
+    /// - `token_program` - The token program of position NFTs, e.g. Token-2022 for positions opened recently
+    /// - `raydium_amm_v3_program` - The Raydium CLMM program ID
+    ///
+    /// # Returns
+    /// - `Vec<PositionNftTokenInfo>` - The candidate position NFTs and their position PDAs. A candidate whose
+    ///   PDA does not exist on chain is not a position NFT.
     // Reference: https://github.com/raydium-io/raydium-clmm/blob/master/client/src/main.rs#L281
-    fn get_nft_account_and_position_by_owner(
+    pub fn get_nft_account_and_position_by_owner(
         &self,
         owner: &Pubkey,
         token_program: Pubkey,
         raydium_amm_v3_program: &Pubkey,
-    ) -> Vec<PositionNftTokenInfo> {
-        let all_tokens = self.rpc
-            .get_token_accounts_by_owner(owner, TokenAccountsFilter::ProgramId(token_program))
-            .unwrap();
-        let mut position_nft_accounts = Vec::new();
-        for keyed_account in all_tokens {
-            if let UiAccountData::Json(parsed_account) = keyed_account.account.data {
-                if parsed_account.program == "spl-token" || parsed_account.program == "spl-token-2022" {
-                    if let Ok(TokenAccountType::Account(ui_token_account)) =
-                        serde_json::from_value(parsed_account.parsed)
-                    {
-                        let _frozen = ui_token_account.state == UiAccountState::Frozen;
-
-                        let token = ui_token_account
-                            .mint
-                            .parse::<Pubkey>()
-                            .unwrap_or_else(|err| panic!("Invalid mint: {}", err));
-                        let token_account = keyed_account
-                            .pubkey
-                            .parse::<Pubkey>()
-                            .unwrap_or_else(|err| panic!("Invalid token account: {}", err));
-                        let token_amount = ui_token_account
-                            .token_amount
-                            .amount
-                            .parse::<u64>()
-                            .unwrap_or_else(|err| panic!("Invalid token amount: {}", err));
-
-                        let _close_authority = ui_token_account.close_authority.map_or(*owner, |s| {
-                            s.parse::<Pubkey>()
-                                .unwrap_or_else(|err| panic!("Invalid close authority: {}", err))
-                        });
-
-                        if ui_token_account.token_amount.decimals == 0 && token_amount == 1 {
-                            let (position_pda, _) = Pubkey::find_program_address(
-                                &[
-                                    raydium_amm_v3::states::POSITION_SEED.as_bytes(),
-                                    token.to_bytes().as_ref(),
-                                ],
-                                &raydium_amm_v3_program,
-                            );
-                            position_nft_accounts.push(PositionNftTokenInfo {
-                                key: token_account,
-                                program: token_program,
-                                position: position_pda,
-                                mint: token,
-                                amount: token_amount,
-                                decimals: ui_token_account.token_amount.decimals,
-                            });
-                        }
+    ) -> Result<Vec<PositionNftTokenInfo>> {
+        let filter = RpcTokenAccountsFilter::ProgramId(token_program.to_string());
+        let token_accounts = self.get_token_accounts_by_owner(owner, filter)?;
+        position_nfts_from_token_accounts(token_accounts, token_program, raydium_amm_v3_program)
+    }
+
+    /// Fetch the token accounts of an owner of a token program or mint, with the data in `account_encoding`
+    fn get_token_accounts_by_owner(
+        &self,
+        owner: &Pubkey,
+        filter: RpcTokenAccountsFilter,
+    ) -> Result<Vec<RpcKeyedAccount>> {
+        let config = RpcAccountInfoConfig {
+            encoding: Some(self.account_encoding.into()),
+            commitment: Some(self.rpc.commitment()),
+            ..RpcAccountInfoConfig::default()
+        };
+        let all_tokens: Response<Vec<RpcKeyedAccount>> = self.rpc.send(
+            RpcRequest::GetTokenAccountsByOwner,
+            json!([owner.to_string(), filter, config]),
+        )?;
+        Ok(all_tokens.value)
+    }
+}
+
+/// The current Unix timestamp in seconds, to bring reward emissions up to date with
+pub(crate) fn unix_timestamp() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs())
+}
+
+fn check_cancelled(cancel: &AtomicBool) -> Result<()> {
+    if cancel.load(Ordering::Relaxed) {
+        return Err(BalanceFetcherError::Cancelled);
+    }
+    Ok(())
+}
+
+/// Add the amounts of token accounts returned by `getTokenAccountsByOwner` to the balances of their mints
+fn add_token_accounts(
+    balances: &mut BTreeMap<Pubkey, TokenBalance>,
+    keyed_accounts: Vec<RpcKeyedAccount>,
+) -> Result<()> {
+    for keyed_account in keyed_accounts {
+        let Some(account) = keyed_account.account.decode::<Account>() else {
+            log::warn!("token account {} cannot be decoded", keyed_account.pubkey);
+            continue;
+        };
+        let Ok(token_account) = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data) else {
+            log::warn!("token account {} cannot be unpacked", keyed_account.pubkey);
+            continue;
+        };
+        let mint = token_account.base.mint;
+        let amount = token_account.base.amount;
+        let balance = balances.entry(mint).or_insert_with(|| TokenBalance {
+            mint,
+            token_program: account.owner,
+            amount: 0,
+            decimals: 0,
+            token_accounts: vec![],
+        });
+        balance.amount = balance.amount.saturating_add(amount);
+        balance.token_accounts.push(TokenAccountBalance { address: Pubkey::from_str(&keyed_account.pubkey)?, amount });
+    }
+    Ok(())
+}
+
+/// `SPLToken` of a wallet without the associated token account of a mint, with the decimals of the mint
+pub(crate) fn missing_spl_token(mint: &Account) -> Result<SPLToken> {
+    let decimals = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint.data)?.base.decimals;
+    Ok(SPLToken { amount: 0, decimals, ownership: Ownership::Wallet, token_program: mint.owner, exists: false })
+}
+
+/// The token program owning a mint account, Token or Token-2022
+pub(crate) fn mint_token_program(mint_address: &Pubkey, mint: &Account) -> Result<Pubkey> {
+    if mint.owner != spl_token::id() && mint.owner != spl_token_2022::id() {
+        return Err(BalanceFetcherError::InvalidInput(format!(
+            "{} is not a mint, it is owned by {}",
+            mint_address, mint.owner
+        )));
+    }
+    Ok(mint.owner)
+}
+
+/// `SPLToken` of an existing token account, with the decimals of its mint
+pub(crate) fn spl_token_from_account(account: &Account, mint: &Account, ownership: Ownership) -> Result<SPLToken> {
+    let amount = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data)?.base.amount;
+    let decimals = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint.data)?.base.decimals;
+    Ok(SPLToken { amount, decimals, ownership, token_program: account.owner, exists: true })
+}
+
+/// Classify the owner of a token account queried for `wallet_address`
+///
+/// # Arguments
+/// - `wallet_address` - The wallet address the token account is queried for
+/// - `owner` - The owner of the token account
+/// - `owner_account` - The account of the owner, `None` if it does not exist on chain (e.g. a fresh wallet)
+pub(crate) fn classify_ownership(wallet_address: &Pubkey, owner: &Pubkey, owner_account: Option<&Account>) -> Ownership {
+    let is_multisig = owner_account.map_or(false, |acc| {
+        (acc.owner == spl_token::id() || acc.owner == spl_token_2022::id())
+            && acc.data.len() == spl_token::state::Multisig::LEN
+    });
+    if is_multisig {
+        Ownership::Multisig { owner: *owner }
+    } else if owner != wallet_address || !owner.is_on_curve() {
+        Ownership::NonWallet { owner: *owner }
+    } else {
+        Ownership::Wallet
+    }
+}
+
+/// Keep the position states among the fetched `positions` PDA accounts that belong to the pool
+pub(crate) fn pool_positions_from_accounts(
+    positions: &[Pubkey],
+    accounts: Vec<Option<Account>>,
+    pool_id: &Pubkey,
+) -> Vec<(Pubkey, raydium_amm_v3::states::PersonalPositionState)> {
+    positions.iter().zip(accounts).filter_map(|(address, p)|
+        match p {
+            None => None,
+            Some(rsp) => {
+                // Positions of other pools are skipped without deserializing them in full
+                match PositionView::new(&rsp.data) {
+                    Ok(view) if view.pool_id() == *pool_id => {}
+                    Ok(_) => return None,
+                    Err(err) => {
+                        log::warn!("position {} cannot be read: {}", address, err);
+                        return None;
+                    }
+                }
+                let position = deserialize_anchor_account::<
+                    raydium_amm_v3::states::PersonalPositionState,
+                >(&rsp);
+                match position {
+                    Err(_) => {
+                        log::warn!("deserialize_anchor_account error");
+                        None
                     }
+                    Ok(position) => Some((*address, position)),
                 }
             }
         }
-        position_nft_accounts
+    ).collect::<Vec<_>>()
+}
+
+/// Collect up to `limit` items of a history paginated newest first, in pages of at most `page_size` items
+///
+/// `fetch_page` gets the last item collected so far, to continue before it, and the number of items to fetch.
+/// Paging stops as soon as `limit` items are collected or a page comes back short, meaning the history ended.
+fn paginate<T, F>(limit: usize, page_size: usize, mut fetch_page: F) -> Result<Vec<T>>
+where
+    F: FnMut(Option<&T>, usize) -> Result<Vec<T>>,
+{
+    let mut items = Vec::new();
+    while items.len() < limit {
+        let page_limit = (limit - items.len()).min(page_size);
+        let page = fetch_page(items.last(), page_limit)?;
+        let ended = page.len() < page_limit;
+        items.extend(page.into_iter().take(page_limit));
+        if ended {
+            break;
+        }
+    }
+    Ok(items)
+}
+
+/// The 256 NFT mint prefixes one byte longer than `prefix`, partitioning the positions it matches
+fn split_prefix(prefix: &[u8]) -> impl DoubleEndedIterator<Item = Vec<u8>> + '_ {
+    (0..=u8::MAX).map(move |byte| [prefix, &[byte][..]].concat())
+}
+
+/// Sum up token 0 and token 1 amounts of positions, failing on overflow
+pub fn total_position_amounts(positions: &[RaydiumPosition]) -> Result<(u64, u64)> {
+    let overflow = || BalanceFetcherError::Math("total amount of positions overflows u64".to_string());
+    positions.iter().try_fold((0u64, 0u64), |(amount_0, amount_1), p| {
+        Ok((
+            amount_0.checked_add(p.amount_0.amount).ok_or_else(overflow)?,
+            amount_1.checked_add(p.amount_1.amount).ok_or_else(overflow)?,
+        ))
+    })
+}
+
+/// Detail position states of a pool, as returned by `raydium_pool_positions`
+///
+/// The amounts are those withdrawable at the pool's current price, as valued by `raydium_position_exit_values`.
+pub(crate) fn raydium_positions(
+    positions: &[(Pubkey, raydium_amm_v3::states::PersonalPositionState)],
+    pool_state: &raydium_amm_v3::states::PoolState,
+) -> Result<Vec<RaydiumPosition>> {
+    // PoolState is packed, copy fields out before use
+    let (decimals_0, decimals_1, tick_current) =
+        (pool_state.mint_decimals_0, pool_state.mint_decimals_1, pool_state.tick_current);
+    positions
+        .iter()
+        .map(|(position, state)| {
+            let amounts = math::position_amounts(state, pool_state)?;
+            Ok(RaydiumPosition {
+                position: *position,
+                nft_mint: state.nft_mint,
+                tick_lower_index: state.tick_lower_index,
+                tick_upper_index: state.tick_upper_index,
+                liquidity: state.liquidity,
+                amount_0: Amount { amount: amounts.amount_0, decimals: decimals_0 },
+                amount_1: Amount { amount: amounts.amount_1, decimals: decimals_1 },
+                in_range: state.tick_lower_index <= tick_current && tick_current < state.tick_upper_index,
+            })
+        })
+        .collect()
+}
+
+/// Pick the position NFTs out of token accounts returned by `getTokenAccountsByOwner`
+pub(crate) fn position_nfts_from_token_accounts(
+    token_accounts: Vec<RpcKeyedAccount>,
+    token_program: Pubkey,
+    raydium_amm_v3_program: &Pubkey,
+) -> Result<Vec<PositionNftTokenInfo>> {
+    let mut position_nft_accounts = Vec::new();
+    for keyed_account in token_accounts {
+        let Some(account) = keyed_account.account.decode::<Account>() else {
+            log::warn!("token account {} cannot be decoded", keyed_account.pubkey);
+            continue;
+        };
+        let Ok(token_account) = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data)
+        else {
+            log::warn!("token account {} cannot be unpacked", keyed_account.pubkey);
+            continue;
+        };
+        // An NFT has supply 1 and 0 decimals. Decimals live in the mint, so leave it to the position PDA
+        // lookup to rule out fungible tokens that happen to have an amount of 1.
+        if token_account.base.amount == 1 {
+            let mint = token_account.base.mint;
+            position_nft_accounts.push(PositionNftTokenInfo {
+                key: Pubkey::from_str(&keyed_account.pubkey)?,
+                program: token_program,
+                position: get_position_pda(&mint, raydium_amm_v3_program),
+                mint,
+                amount: token_account.base.amount,
+            });
+        }
     }
+    Ok(position_nft_accounts)
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-struct PositionNftTokenInfo {
-    key: Pubkey,
-    program: Pubkey,
-    position: Pubkey,
-    mint: Pubkey,
-    amount: u64,
-    decimals: u8,
+pub struct PositionNftTokenInfo {
+    /// The token account holding the NFT
+    pub key: Pubkey,
+    pub program: Pubkey,
+    pub position: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+/// Nearest-rank percentile of the values, 0 if there is none
+pub(crate) fn percentile(mut values: Vec<u64>, percentile: f64) -> u64 {
+    if values.is_empty() {
+        return 0;
+    }
+    values.sort_unstable();
+    let rank = (percentile * values.len() as f64).ceil() as usize;
+    values[rank.clamp(1, values.len()) - 1]
+}
+
+fn get_position_pda(nft_mint: &Pubkey, raydium_amm_v3_program: &Pubkey) -> Pubkey {
+    let (position_pda, _) = Pubkey::find_program_address(
+        &[
+            raydium_amm_v3::states::POSITION_SEED.as_bytes(),
+            nft_mint.to_bytes().as_ref(),
+        ],
+        raydium_amm_v3_program,
+    );
+    position_pda
+}
+
+fn get_tick_array_pda(pool_id: &Pubkey, start_index: i32, raydium_amm_v3_program: &Pubkey) -> Pubkey {
+    let (tick_array_pda, _) = Pubkey::find_program_address(
+        &[
+            raydium_amm_v3::states::TICK_ARRAY_SEED.as_bytes(),
+            pool_id.as_ref(),
+            &start_index.to_be_bytes(),
+        ],
+        raydium_amm_v3_program,
+    );
+    tick_array_pda
 }
 
 pub fn deserialize_anchor_account<T: AccountDeserialize>(account: &Account) -> Result<T> {
     let mut data: &[u8] = &account.data;
-    T::try_deserialize(&mut data).map_err(Into::into)
+    T::try_deserialize(&mut data).map_err(|err| BalanceFetcherError::Deserialize(err.to_string()))
 }
 
 #[cfg(test)]
@@ -275,25 +1905,412 @@ mod tests {
         assert!(balance_sol_unified > balance_sol);
     }
 
+    #[test]
+    fn test_compare_balance_sol() {
+        let fetcher = new_balancer_fetcher();
+        let other = new_balancer_fetcher();
+        let pubkey = Pubkey::from_str("53zSj4G935ZY2a5x2UnGAiJXSuXXmGHaLph2zhAUvYpg").unwrap();
+        let consistency = fetcher.compare_balance_sol(&other, &pubkey, 10).unwrap();
+        assert!(consistency.consistent);
+    }
+
     #[test]
     fn test_balance_spl_token() {
         let balancer_fetcher = new_balancer_fetcher();
         let wallet = Pubkey::from_str("5tzFkiKscXHK5ZXCGbXZxdw7gTjjD1mBwuoFbhUvuAi9").unwrap();
-        // WSOL (Wrapped SOL) mint address
-        let token_mint_address = Pubkey::from_str("So11111111111111111111111111111111111111112").unwrap();
+        let token_mint_address = MintAddress::wsol();
         let balance_spl_token = balancer_fetcher.balance_spl_token(&wallet, &token_mint_address).unwrap();
         assert!(balance_spl_token.amount > 0);
         assert_eq!(balance_spl_token.decimals, 9);
+        assert_eq!(balance_spl_token.ownership, Ownership::Wallet);
+        assert_eq!(balance_spl_token.token_program, spl_token::id());
+        assert!(balance_spl_token.exists);
     }
 
     #[test]
-    fn test_get_raydium_pool_position() {
+    fn test_spl_token_from_account() {
+        let mut mint_data = vec![0; spl_token::state::Mint::LEN];
+        let mint_state = spl_token::state::Mint { decimals: 6, is_initialized: true, ..Default::default() };
+        mint_state.pack_into_slice(&mut mint_data);
+        let mint = Account { owner: spl_token::id(), data: mint_data, ..Account::default() };
+        let mut account_data = vec![0; spl_token::state::Account::LEN];
+        let account_state = spl_token::state::Account {
+            amount: 42,
+            state: spl_token::state::AccountState::Initialized,
+            ..Default::default()
+        };
+        account_state.pack_into_slice(&mut account_data);
+        let account = Account { owner: spl_token::id(), data: account_data, ..Account::default() };
+        let balance = spl_token_from_account(&account, &mint, Ownership::Wallet).unwrap();
+        assert_eq!(balance.amount, 42);
+        assert_eq!(balance.decimals, 6);
+        assert_eq!(balance.token_program, spl_token::id());
+        assert!(balance.exists);
+        assert!(spl_token_from_account(&mint, &mint, Ownership::Wallet).is_err());
+    }
+
+    #[test]
+    fn test_balance_spl_token_2022() {
+        let balancer_fetcher = new_balancer_fetcher();
+        // PYUSD is a Token-2022 mint, a fresh wallet has no token account of it
+        let pyusd = MintAddress::from_str("2b1kV6DkPAnxd5ixfnxCpjxmKwqjjaYmCZfHsFu24GXo").unwrap();
+        let balance = balancer_fetcher.balance_spl_token(&Pubkey::new_unique(), &pyusd).unwrap();
+        assert_eq!(balance.amount, 0);
+        assert_eq!(balance.token_program, spl_token_2022::id());
+        assert!(!balance.exists);
+        assert_eq!(balance.decimals, 6);
+        // A wallet is not a mint
+        let wallet = Pubkey::from_str("5tzFkiKscXHK5ZXCGbXZxdw7gTjjD1mBwuoFbhUvuAi9").unwrap();
+        let err = balancer_fetcher.balance_spl_token(&wallet, &MintAddress::from(wallet)).unwrap_err();
+        assert!(matches!(err, BalanceFetcherError::InvalidInput(_)));
+    }
+
+    #[test]
+    fn test_all_token_balances() {
+        let balancer_fetcher = new_balancer_fetcher();
+        let wallet = Pubkey::from_str("5tzFkiKscXHK5ZXCGbXZxdw7gTjjD1mBwuoFbhUvuAi9").unwrap();
+        let balances = balancer_fetcher.all_token_balances(&wallet).unwrap();
+        let wsol = Pubkey::from_str("So11111111111111111111111111111111111111112").unwrap();
+        let wsol_balance = balances.iter().find(|balance| balance.mint == wsol).unwrap();
+        assert!(wsol_balance.amount > 0);
+        assert_eq!(wsol_balance.decimals, 9);
+        assert_eq!(wsol_balance.token_program, spl_token::id());
+        assert!(balances.iter().all(|balance| !balance.token_accounts.is_empty()));
+        assert!(balances.windows(2).all(|pair| pair[0].mint < pair[1].mint));
+    }
+
+    #[test]
+    fn test_balance_spl_token_all_accounts() {
+        let balancer_fetcher = new_balancer_fetcher();
+        let wallet = Pubkey::from_str("5tzFkiKscXHK5ZXCGbXZxdw7gTjjD1mBwuoFbhUvuAi9").unwrap();
+        let wsol = MintAddress::wsol();
+        let balance = balancer_fetcher.balance_spl_token_all_accounts(&wallet, &wsol).unwrap();
+        assert_eq!(balance.decimals, 9);
+        let ata_balance = balancer_fetcher.balance_spl_token(&wallet, &wsol).unwrap();
+        assert!(balance.amount >= ata_balance.amount);
+        assert_eq!(balance.amount, balance.token_accounts.iter().map(|account| account.amount).sum::<u64>());
+        // A fresh wallet has no token account but the mint's decimals
+        let fresh_balance = balancer_fetcher.balance_spl_token_all_accounts(&Pubkey::new_unique(), &wsol).unwrap();
+        assert_eq!((fresh_balance.amount, fresh_balance.decimals), (0, 9));
+        assert!(fresh_balance.token_accounts.is_empty());
+    }
+
+    #[test]
+    fn test_token_metadata() {
+        let balancer_fetcher = new_balancer_fetcher();
+        let usdc = Pubkey::from_str("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v").unwrap();
+        let metadata = balancer_fetcher.token_metadata(&[usdc, Pubkey::new_unique()]).unwrap();
+        assert_eq!(metadata[0].as_ref().unwrap().symbol, "USDC");
+        assert_eq!(metadata[1], None);
+    }
+
+    #[test]
+    fn test_fee_spend() {
         let fetcher = new_balancer_fetcher();
         let wallet = Pubkey::from_str("53zSj4G935ZY2a5x2UnGAiJXSuXXmGHaLph2zhAUvYpg").unwrap();
+        let fee_spend = fetcher.fee_spend(&wallet, None, 10).unwrap();
+        assert!(fee_spend.transactions.len() <= 10);
+        assert!(fee_spend.transactions.iter().all(|tx| tx.base_fee >= LAMPORTS_PER_SIGNATURE));
+        assert_eq!(fee_spend.total_fee(), fee_spend.base_fee() + fee_spend.priority_fee());
+    }
+
+    #[test]
+    fn test_paginate() {
+        // A history of 25 items, newest first
+        let history: Vec<u32> = (0..25).rev().collect();
+        let page_limits = std::cell::RefCell::new(Vec::new());
+        let fetch_page = |last: Option<&u32>, page_limit: usize| -> Result<Vec<u32>> {
+            page_limits.borrow_mut().push(page_limit);
+            let start = last.map_or(0, |last| history.iter().position(|item| item == last).unwrap() + 1);
+            Ok(history[start..].iter().take(page_limit).copied().collect())
+        };
+        assert_eq!(paginate(12, 5, fetch_page).unwrap(), history[..12].to_vec());
+        assert_eq!(page_limits.take(), vec![5, 5, 2]);
+        assert_eq!(paginate(100, 10, fetch_page).unwrap(), history);
+        assert_eq!(page_limits.take(), vec![10, 10, 10]);
+        assert!(paginate(0, 10, fetch_page).unwrap().is_empty());
+        assert!(page_limits.take().is_empty());
+
+        // A page longer than asked for is cut
+        let items = paginate(3, 10, |_, _| Ok(vec![1, 2, 3, 4, 5])).unwrap();
+        assert_eq!(items, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_split_prefix() {
+        let prefixes: Vec<Vec<u8>> = split_prefix(&[]).collect();
+        assert_eq!(prefixes.len(), 256);
+        assert_eq!(prefixes[0], vec![0]);
+        assert_eq!(prefixes[255], vec![255]);
+        let prefixes: Vec<Vec<u8>> = split_prefix(&[7]).rev().collect();
+        assert_eq!(prefixes[0], vec![7, 255]);
+        assert_eq!(prefixes[255], vec![7, 0]);
+    }
+
+    #[test]
+    fn test_percentile() {
+        assert_eq!(percentile(vec![], 0.95), 0);
+        assert_eq!(percentile(vec![7], 0.95), 7);
+        assert_eq!(percentile((1..=100).rev().collect(), 0.95), 95);
+        assert_eq!(percentile(vec![1, 2, 3, 4], 0.5), 2);
+    }
+
+    #[test]
+    fn test_missing_token_accounts() {
+        let fetcher = new_balancer_fetcher();
+        let wallet = Pubkey::from_str("5tzFkiKscXHK5ZXCGbXZxdw7gTjjD1mBwuoFbhUvuAi9").unwrap();
+        let wsol = MintAddress::wsol();
+        // A fresh wallet has no token account at all
+        let fresh_wallet = Pubkey::new_unique();
+        assert!(fetcher.missing_token_accounts(&wallet, &[wsol]).unwrap().is_empty());
+        let missing = fetcher.missing_token_accounts(&fresh_wallet, &[wsol]).unwrap();
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].token_program, spl_token::id());
+        assert!(missing[0].rent > 0);
+    }
+
+    #[test]
+    fn test_account_overview() {
+        let fetcher = new_balancer_fetcher();
+        // Raydium CLMM program
+        let program = Pubkey::from_str(RAYDIUM_V3_PROGRAM_ID).unwrap();
+        let overview = fetcher.account_overview(&program).unwrap();
+        assert!(overview.executable);
+        assert!(overview.is_rent_exempt());
         // SOL-USDC.1bp Pool
-        let pool_id = Pubkey::from_str("8sLbNZoA1cfnvMJLPfp98ZLAnFSYCFApfJKMbiXNLwxj").unwrap();
+        let pool_id = Pubkey::from_str(SOL_USDC_1BP_POOL_ID).unwrap();
+        let overview = fetcher.account_overview(&pool_id).unwrap();
+        assert_eq!(overview.owner, program);
+        assert!(!overview.executable);
+        assert!(overview.data_len > 0);
+    }
+
+    #[test]
+    fn test_classify_address() {
+        let fetcher = new_balancer_fetcher();
+        let wallet = Pubkey::from_str("5tzFkiKscXHK5ZXCGbXZxdw7gTjjD1mBwuoFbhUvuAi9").unwrap();
+        assert_eq!(fetcher.classify_address(&wallet).unwrap(), AddressKind::Wallet);
+        let wsol = Pubkey::from_str(WSOL_MINT_ADDRESS).unwrap();
+        let token_account = spl_associated_token_account::get_associated_token_address(&wallet, &wsol);
+        match fetcher.classify_address(&token_account).unwrap() {
+            AddressKind::TokenAccount(info) => {
+                assert_eq!(info.mint, wsol);
+                assert_eq!(info.owner, wallet);
+                assert_eq!(info.amount.decimals, 9);
+            }
+            kind => panic!("unexpected address kind {:?}", kind),
+        }
+        assert_eq!(fetcher.classify_address(&wsol).unwrap(), AddressKind::Other { owner: spl_token::id() });
+    }
+
+    #[test]
+    fn test_mint_top_holders() {
+        let fetcher = new_balancer_fetcher();
+        let token_mint_address = MintAddress::usdc();
+        let mint_holders = fetcher.mint_top_holders(&token_mint_address).unwrap();
+        assert_eq!(mint_holders.decimals, 6);
+        assert!(!mint_holders.holders.is_empty());
+        assert!(mint_holders.holders.iter().all(|h| h.amount <= mint_holders.supply));
+    }
+
+    #[test]
+    fn test_mint_info() {
+        let fetcher = new_balancer_fetcher();
+        let token_mint_address = MintAddress::wsol();
+        let mint_info = fetcher.mint_info(&token_mint_address).unwrap();
+        assert_eq!(mint_info.decimals, 9);
+        assert_eq!(mint_info.mint_authority, None);
+        assert_eq!(mint_info.freeze_authority, None);
+    }
+
+    #[test]
+    fn test_get_raydium_pool_position() {
+        let fetcher = new_balancer_fetcher();
+        let wallet = Pubkey::from_str("53zSj4G935ZY2a5x2UnGAiJXSuXXmGHaLph2zhAUvYpg").unwrap();
+        let pool_id = PoolId::sol_usdc_1bp();
         let (amount_0, amount_1) = fetcher.raydium_pool_position(&wallet, &pool_id).unwrap();
         assert!(amount_0 > 0);
         assert!(amount_1 > 0);
+        let positions = fetcher.raydium_pool_positions(&wallet, &pool_id).unwrap();
+        let sum_0: u64 = positions.iter().map(|p| p.amount_0.amount).sum();
+        let sum_1: u64 = positions.iter().map(|p| p.amount_1.amount).sum();
+        assert_eq!((amount_0, amount_1), (sum_0, sum_1));
+    }
+
+    #[test]
+    fn test_raydium_pool_positions() {
+        let fetcher = new_balancer_fetcher();
+        let wallet = Pubkey::from_str("53zSj4G935ZY2a5x2UnGAiJXSuXXmGHaLph2zhAUvYpg").unwrap();
+        let pool_id = PoolId::sol_usdc_1bp();
+        let positions = fetcher.raydium_pool_positions(&wallet, &pool_id).unwrap();
+        assert!(!positions.is_empty());
+        assert!(positions.iter().all(|p| p.tick_lower_index < p.tick_upper_index));
+        // Out of range, a position holds a single token
+        assert!(positions.iter().all(|p| p.in_range || p.amount_0.amount == 0 || p.amount_1.amount == 0));
+        total_position_amounts(&positions).unwrap();
+    }
+
+    #[test]
+    fn test_raydium_positions() {
+        let pool_state = raydium_amm_v3::states::PoolState {
+            sqrt_price_x64: math::sqrt_price_at_tick(0).unwrap(),
+            mint_decimals_0: 9,
+            mint_decimals_1: 6,
+            ..Default::default()
+        };
+        let position = |tick_lower_index, tick_upper_index| {
+            let state = raydium_amm_v3::states::PersonalPositionState {
+                tick_lower_index,
+                tick_upper_index,
+                liquidity: 1_000_000_000,
+                ..Default::default()
+            };
+            (Pubkey::new_unique(), state)
+        };
+        let positions = raydium_positions(&[position(-100, 100), position(100, 200)], &pool_state).unwrap();
+        assert!(positions[0].in_range && positions[0].amount_0.amount > 0 && positions[0].amount_1.amount > 0);
+        // Above the current price, the position is all token 0
+        assert!(!positions[1].in_range && positions[1].amount_0.amount > 0);
+        assert_eq!(positions[1].amount_1, Amount { amount: 0, decimals: 6 });
+        let (amount_0, amount_1) = total_position_amounts(&positions).unwrap();
+        assert_eq!(amount_0, positions[0].amount_0.amount + positions[1].amount_0.amount);
+        assert_eq!(amount_1, positions[0].amount_1.amount);
+        let overflowing =
+            RaydiumPosition { amount_0: Amount { amount: u64::MAX, decimals: 9 }, ..positions[1].clone() };
+        assert!(matches!(
+            total_position_amounts(&[overflowing.clone(), overflowing]),
+            Err(BalanceFetcherError::Math(_))
+        ));
+    }
+
+    #[test]
+    fn test_raydium_pool_position_fees() {
+        let fetcher = new_balancer_fetcher();
+        let wallet = Pubkey::from_str("53zSj4G935ZY2a5x2UnGAiJXSuXXmGHaLph2zhAUvYpg").unwrap();
+        let pool_id = PoolId::sol_usdc_1bp();
+        let (fee_0, fee_1) = fetcher.raydium_pool_position_fees(&wallet, &pool_id).unwrap();
+        assert!(fee_0 > 0 || fee_1 > 0);
+        // A wallet without positions has no fees
+        let fees = fetcher.raydium_pool_position_fees(&Pubkey::new_unique(), &pool_id).unwrap();
+        assert_eq!(fees, (0, 0));
+    }
+
+    #[test]
+    fn test_all_raydium_positions() {
+        let fetcher = new_balancer_fetcher();
+        let wallet = Pubkey::from_str("53zSj4G935ZY2a5x2UnGAiJXSuXXmGHaLph2zhAUvYpg").unwrap();
+        let pool_id = Pubkey::from_str(SOL_USDC_1BP_POOL_ID).unwrap();
+        let all_positions = fetcher.all_raydium_positions(&wallet).unwrap();
+        let sol_usdc = all_positions.iter().find(|pool| pool.pool_id == pool_id).unwrap();
+        assert!(!sol_usdc.positions.is_empty());
+        assert_eq!(sol_usdc.mint_0, Pubkey::from_str(WSOL_MINT_ADDRESS).unwrap());
+        assert_eq!(sol_usdc.amount_0.amount, sol_usdc.positions.iter().map(|p| p.amount_0.amount).sum::<u64>());
+        assert!(fetcher.all_raydium_positions(&Pubkey::new_unique()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_raydium_pool_position_rewards() {
+        let fetcher = new_balancer_fetcher();
+        let wallet = Pubkey::from_str("53zSj4G935ZY2a5x2UnGAiJXSuXXmGHaLph2zhAUvYpg").unwrap();
+        let pool_id = PoolId::sol_usdc_1bp();
+        let rewards = fetcher.raydium_pool_position_rewards(&wallet, &pool_id).unwrap();
+        assert!(rewards.len() <= 3);
+        // A wallet without positions has the pool's reward mints with nothing pending
+        let rewards = fetcher.raydium_pool_position_rewards(&Pubkey::new_unique(), &pool_id).unwrap();
+        assert!(rewards.iter().all(|reward| reward.amount == 0));
+    }
+
+    #[test]
+    fn test_account_encoding() {
+        let fetcher = new_balancer_fetcher();
+        let zstd_fetcher = new_balancer_fetcher().with_account_encoding(AccountEncoding::Base64Zstd);
+        let wallet = Pubkey::from_str("53zSj4G935ZY2a5x2UnGAiJXSuXXmGHaLph2zhAUvYpg").unwrap();
+        let raydium_v3_program = Pubkey::from_str(RAYDIUM_V3_PROGRAM_ID).unwrap();
+        let positions = fetcher
+            .get_nft_account_and_position_by_owner(&wallet, spl_token_2022::id(), &raydium_v3_program)
+            .unwrap();
+        let zstd_positions = zstd_fetcher
+            .get_nft_account_and_position_by_owner(&wallet, spl_token_2022::id(), &raydium_v3_program)
+            .unwrap();
+        assert_eq!(positions, zstd_positions);
+    }
+
+    #[test]
+    fn test_get_raydium_pool_position_by_nft_mints() {
+        let fetcher = new_balancer_fetcher();
+        let wallet = Pubkey::from_str("53zSj4G935ZY2a5x2UnGAiJXSuXXmGHaLph2zhAUvYpg").unwrap();
+        let pool_id = PoolId::sol_usdc_1bp();
+        let raydium_v3_program = Pubkey::from_str(RAYDIUM_V3_PROGRAM_ID).unwrap();
+        let nft_mints: Vec<Pubkey> = fetcher
+            .get_nft_account_and_position_by_owner(&wallet, spl_token_2022::id(), &raydium_v3_program)
+            .unwrap()
+            .iter()
+            .map(|item| item.mint)
+            .collect();
+        let by_mints = fetcher.raydium_pool_position_by_nft_mints(&nft_mints, &pool_id).unwrap();
+        let by_wallet = fetcher.raydium_pool_position(&wallet, &pool_id).unwrap();
+        assert_eq!(by_mints, by_wallet);
+    }
+
+    #[test]
+    fn test_pool_price() {
+        let fetcher = new_balancer_fetcher();
+        let price = fetcher.pool_price(&PoolId::sol_usdc_1bp()).unwrap();
+        assert_eq!((price.decimals_0, price.decimals_1), (9, 6));
+        // SOL is worth some USDC, unlike 1 USDC and more than 100k USDC
+        assert!(price.price_0_in_1 > 1.0 && price.price_0_in_1 < 100_000.0);
+        assert!((price.price_0_in_1 * price.price_1_in_0 - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_raydium_pool_stats() {
+        let fetcher = new_balancer_fetcher();
+        let pool_id = PoolId::sol_usdc_1bp();
+        let stats = fetcher.raydium_pool_stats(&pool_id).unwrap();
+        assert!(stats.open_positions > 0);
+        assert!(stats.positions >= stats.open_positions);
+        assert!(stats.in_range_liquidity <= stats.total_liquidity);
+    }
+
+    #[test]
+    fn test_raydium_pool_stats_cancelled() {
+        let fetcher = new_balancer_fetcher();
+        let pool_id = PoolId::sol_usdc_1bp();
+        let cancel = AtomicBool::new(true);
+        let err = fetcher.raydium_pool_stats_cancellable(&pool_id, |_| {}, &cancel).unwrap_err();
+        assert!(matches!(err, BalanceFetcherError::Cancelled));
+    }
+
+    #[test]
+    fn test_check_rpc() {
+        let fetcher = new_balancer_fetcher();
+        let checks = fetcher.check_rpc();
+        assert_eq!(checks.len(), 5);
+        // The public endpoint may refuse the filtered getProgramAccounts, but not the basic methods
+        assert!(checks[0].result.is_ok());
+        assert!(checks[1].result.is_ok());
+    }
+
+    #[test]
+    fn test_quote_swap() {
+        let fetcher = new_balancer_fetcher();
+        // SOL-USDC.1bp Pool, swap 1 SOL to USDC
+        let pool_id = PoolId::sol_usdc_1bp();
+        let quote = fetcher.quote_swap(&pool_id, 1_000_000_000, SwapDirection::ZeroForOne).unwrap();
+        assert_eq!(quote.amount_in, 1_000_000_000);
+        assert!(quote.amount_out > 0);
+        assert!(quote.price_impact > 0.0 && quote.price_impact < 0.01);
+    }
+
+    #[test]
+    fn test_position_exit_values_sol_usdc_1bp() {
+        let fetcher = new_balancer_fetcher();
+        let wallet = Pubkey::from_str("53zSj4G935ZY2a5x2UnGAiJXSuXXmGHaLph2zhAUvYpg").unwrap();
+        let exit_values = fetcher.position_exit_values_sol_usdc_1bp(&wallet).unwrap();
+        assert!(!exit_values.is_empty());
+        for exit_value in exit_values {
+            assert!(exit_value.exit_amount >= exit_value.amount_1);
+            assert!(exit_value.price_impact >= 0.0);
+        }
     }
 }
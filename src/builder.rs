@@ -0,0 +1,206 @@
+use std::str::FromStr;
+use std::time::Duration;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use solana_client::nonblocking::rpc_client::RpcClient as AsyncRpcClient;
+use solana_client::rpc_client::{RpcClient, RpcClientConfig};
+use solana_sdk::commitment_config::CommitmentConfig;
+use crate::async_balance_fetcher::AsyncBalanceFetcher;
+use crate::balance_fetcher::{AccountEncoding, BalanceFetcher};
+use crate::cache::CacheTtls;
+use crate::connection_manager::ConnectionManager;
+use crate::error::{BalanceFetcherError, Result};
+use crate::rate_limiter::RateLimiter;
+use crate::rpc_sender::{HttpRpcSender, RetryPolicy};
+
+/// Default timeout of a single RPC request, the same as the RPC client's
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Configure a `BalanceFetcher` beyond the defaults of `BalanceFetcher::new`
+///
+/// ```ignore
+/// let fetcher = BalanceFetcher::builder("https://rpc.example.com")
+///     .commitment(CommitmentConfig::finalized())
+///     .timeout(Duration::from_secs(10))
+///     .header("x-api-key", "secret")
+///     .retry_policy(RetryPolicy { max_attempts: 10, ..RetryPolicy::default() })
+///     .rate_limit(10.0)
+///     .fallback_rpc_url("https://fallback.example.com")
+///     .failback_after(Duration::from_secs(300))
+///     .build()?;
+/// ```
+pub struct BalanceFetcherBuilder {
+    rpc_url: String,
+    fallback_rpc_urls: Vec<String>,
+    failback_after: Option<Duration>,
+    commitment: CommitmentConfig,
+    timeout: Duration,
+    headers: Vec<(String, String)>,
+    retry_policy: RetryPolicy,
+    rate_limit: Option<f64>,
+    cache: Option<CacheTtls>,
+    account_encoding: AccountEncoding,
+}
+
+impl BalanceFetcher {
+    pub fn builder<T: ToString>(rpc_url: T) -> BalanceFetcherBuilder {
+        BalanceFetcherBuilder {
+            rpc_url: rpc_url.to_string(),
+            fallback_rpc_urls: vec![],
+            failback_after: None,
+            commitment: CommitmentConfig::default(),
+            timeout: DEFAULT_TIMEOUT,
+            headers: vec![],
+            retry_policy: RetryPolicy::default(),
+            rate_limit: None,
+            cache: None,
+            account_encoding: AccountEncoding::default(),
+        }
+    }
+}
+
+impl BalanceFetcherBuilder {
+    /// Fail over to this endpoint when the previous one times out or fails with a server error
+    ///
+    /// The endpoints are tried in the order they are added, after the one passed to `BalanceFetcher::builder`.
+    /// Failing over takes an attempt of the retry policy, without waiting.
+    pub fn fallback_rpc_url<T: ToString>(mut self, rpc_url: T) -> Self {
+        self.fallback_rpc_urls.push(rpc_url.to_string());
+        self
+    }
+
+    /// Return to the primary endpoint once failed over for `failback_after`, instead of staying on the fallback
+    pub fn failback_after(mut self, failback_after: Duration) -> Self {
+        self.failback_after = Some(failback_after);
+        self
+    }
+
+    /// Commitment level of all queries, `finalized` by default
+    pub fn commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.commitment = commitment;
+        self
+    }
+
+    /// Timeout of a single attempt of an RPC request
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Send an HTTP header with every request, e.g. the API key of an authenticated RPC provider
+    pub fn header<K: ToString, V: ToString>(mut self, name: K, value: V) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Retry requests failing transiently, `RetryPolicy::default()` by default
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Send at most `requests_per_second` requests per second, shared by all methods of the fetcher
+    ///
+    /// Unlimited by default. Bursts of up to one second worth of requests are sent right away.
+    pub fn rate_limit(mut self, requests_per_second: f64) -> Self {
+        self.rate_limit = Some(requests_per_second);
+        self
+    }
+
+    /// See `BalanceFetcher::with_cache`, no caching by default
+    pub fn cache(mut self, ttls: CacheTtls) -> Self {
+        self.cache = Some(ttls);
+        self
+    }
+
+    /// See `BalanceFetcher::with_account_encoding`
+    pub fn account_encoding(mut self, account_encoding: AccountEncoding) -> Self {
+        self.account_encoding = account_encoding;
+        self
+    }
+
+    /// Build the `BalanceFetcher`, failing if a header name or value or the rate limit is invalid
+    pub fn build(self) -> Result<BalanceFetcher> {
+        let sender = self.sender()?;
+        let rpc = RpcClient::new_sender(sender, RpcClientConfig::with_commitment(self.commitment));
+        let balance_fetcher = BalanceFetcher::from_rpc(rpc).with_account_encoding(self.account_encoding);
+        Ok(match self.cache {
+            Some(ttls) => balance_fetcher.with_cache(ttls),
+            None => balance_fetcher,
+        })
+    }
+
+    /// Build an `AsyncBalanceFetcher` with the same options, failing like `build`
+    pub fn build_async(self) -> Result<AsyncBalanceFetcher> {
+        let sender = self.sender()?;
+        let rpc = AsyncRpcClient::new_sender(sender, RpcClientConfig::with_commitment(self.commitment));
+        let balance_fetcher = AsyncBalanceFetcher::from_rpc(rpc).with_account_encoding(self.account_encoding);
+        Ok(match self.cache {
+            Some(ttls) => balance_fetcher.with_cache(ttls),
+            None => balance_fetcher,
+        })
+    }
+
+    fn sender(&self) -> Result<HttpRpcSender> {
+        let rate_limiter = match self.rate_limit {
+            Some(rate_limit) if !(rate_limit.is_finite() && rate_limit > 0.0) => {
+                return Err(BalanceFetcherError::InvalidInput(format!("rate limit {} must be positive", rate_limit)));
+            }
+            rate_limit => rate_limit.map(RateLimiter::new),
+        };
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let header_name = HeaderName::from_str(name)
+                .map_err(|_| BalanceFetcherError::InvalidInput(format!("invalid header name {:?}", name)))?;
+            let header_value = HeaderValue::from_str(value)
+                .map_err(|_| BalanceFetcherError::InvalidInput(format!("invalid value of header {}", name)))?;
+            headers.insert(header_name, header_value);
+        }
+        let client = reqwest::Client::builder().default_headers(headers).timeout(self.timeout).build()?;
+        let endpoints = [vec![self.rpc_url.clone()], self.fallback_rpc_urls.clone()].concat();
+        let connections = ConnectionManager::new(endpoints, self.failback_after);
+        Ok(HttpRpcSender::new(client, connections, self.retry_policy, rate_limiter))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_invalid_header() {
+        let result = BalanceFetcher::builder("https://api.mainnet-beta.solana.com")
+            .header("x api key", "secret")
+            .build();
+        assert!(matches!(result, Err(BalanceFetcherError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_build_invalid_rate_limit() {
+        let result = BalanceFetcher::builder("https://api.mainnet-beta.solana.com").rate_limit(0.0).build();
+        assert!(matches!(result, Err(BalanceFetcherError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_build_commitment() {
+        let fetcher = BalanceFetcher::builder("https://api.mainnet-beta.solana.com")
+            .commitment(CommitmentConfig::confirmed())
+            .header("x-api-key", "secret")
+            .build()
+            .unwrap();
+        assert_eq!(fetcher.rpc.commitment(), CommitmentConfig::confirmed());
+        assert_eq!(fetcher.rpc.url(), "https://api.mainnet-beta.solana.com");
+    }
+
+    #[test]
+    fn test_build_async() {
+        let fetcher = BalanceFetcher::builder("https://api.mainnet-beta.solana.com")
+            .commitment(CommitmentConfig::confirmed())
+            .fallback_rpc_url("https://fallback.example.com")
+            .build_async()
+            .unwrap();
+        assert_eq!(fetcher.rpc.commitment(), CommitmentConfig::confirmed());
+        assert_eq!(fetcher.rpc.url(), "https://api.mainnet-beta.solana.com");
+        let result = BalanceFetcher::builder("https://api.mainnet-beta.solana.com").rate_limit(-1.0).build_async();
+        assert!(matches!(result, Err(BalanceFetcherError::InvalidInput(_))));
+    }
+}
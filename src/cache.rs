@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use solana_sdk::account::Account;
+use solana_sdk::pubkey::Pubkey;
+
+/// How long `BalanceFetcher` reuses fetched data of each kind, zero to always refetch it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CacheTtls {
+    /// Pool accounts, whose price and liquidity change with every swap
+    pub pool: Duration,
+    /// Mint accounts, whose decimals never change and supply rarely matters to the minute
+    pub mint: Duration,
+    /// AMM config accounts, holding the fee rates of pools
+    pub amm_config: Duration,
+    /// Metaplex token metadata accounts, holding the names and symbols of mints
+    pub metadata: Duration,
+    /// Position PDAs of a wallet, which change only when it opens or closes a position
+    pub position_pdas: Duration,
+}
+
+impl Default for CacheTtls {
+    /// Pools for 10 seconds, mints, AMM configs and token metadata for an hour, position PDAs for a minute
+    fn default() -> Self {
+        Self {
+            pool: Duration::from_secs(10),
+            mint: Duration::from_secs(3600),
+            amm_config: Duration::from_secs(3600),
+            metadata: Duration::from_secs(3600),
+            position_pdas: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Kind of a cached account, selecting its TTL
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum AccountKind {
+    Pool,
+    Mint,
+    AmmConfig,
+    Metadata,
+}
+
+/// Values cached by key until their TTL has passed
+struct TtlMap<K, V> {
+    entries: Mutex<HashMap<K, (Instant, V)>>,
+}
+
+impl<K: Eq + Hash, V: Clone> TtlMap<K, V> {
+    fn new() -> Self {
+        Self { entries: Mutex::new(HashMap::new()) }
+    }
+
+    fn get(&self, key: &K, ttl: Duration) -> Option<V> {
+        let entries = self.entries.lock().unwrap();
+        let (fetched_at, value) = entries.get(key)?;
+        (fetched_at.elapsed() < ttl).then(|| value.clone())
+    }
+
+    fn insert(&self, key: K, value: V) {
+        self.entries.lock().unwrap().insert(key, (Instant::now(), value));
+    }
+}
+
+/// In-memory cache of accounts and position PDAs shared by all methods of a `BalanceFetcher`
+pub(crate) struct AccountCache {
+    ttls: CacheTtls,
+    accounts: TtlMap<Pubkey, Account>,
+    position_pdas: TtlMap<Pubkey, Vec<Pubkey>>,
+}
+
+impl AccountCache {
+    pub(crate) fn new(ttls: CacheTtls) -> Self {
+        Self { ttls, accounts: TtlMap::new(), position_pdas: TtlMap::new() }
+    }
+
+    fn ttl(&self, kind: AccountKind) -> Duration {
+        match kind {
+            AccountKind::Pool => self.ttls.pool,
+            AccountKind::Mint => self.ttls.mint,
+            AccountKind::AmmConfig => self.ttls.amm_config,
+            AccountKind::Metadata => self.ttls.metadata,
+        }
+    }
+
+    pub(crate) fn account(&self, address: &Pubkey, kind: AccountKind) -> Option<Account> {
+        self.accounts.get(address, self.ttl(kind))
+    }
+
+    pub(crate) fn insert_account(&self, address: Pubkey, account: Account) {
+        self.accounts.insert(address, account);
+    }
+
+    pub(crate) fn position_pdas(&self, wallet_address: &Pubkey) -> Option<Vec<Pubkey>> {
+        self.position_pdas.get(wallet_address, self.ttls.position_pdas)
+    }
+
+    pub(crate) fn insert_position_pdas(&self, wallet_address: Pubkey, position_pdas: Vec<Pubkey>) {
+        self.position_pdas.insert(wallet_address, position_pdas);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_account_cache_ttl() {
+        let ttls = CacheTtls { pool: Duration::ZERO, ..CacheTtls::default() };
+        let cache = AccountCache::new(ttls);
+        let address = Pubkey::new_unique();
+        assert_eq!(cache.account(&address, AccountKind::Mint), None);
+        let account = Account { lamports: 1, ..Account::default() };
+        cache.insert_account(address, account.clone());
+        assert_eq!(cache.account(&address, AccountKind::Mint), Some(account));
+        // A zero TTL never hits
+        assert_eq!(cache.account(&address, AccountKind::Pool), None);
+    }
+
+    #[test]
+    fn test_position_pdas_cache() {
+        let cache = AccountCache::new(CacheTtls::default());
+        let wallet = Pubkey::new_unique();
+        let pdas = vec![Pubkey::new_unique()];
+        cache.insert_position_pdas(wallet, pdas.clone());
+        assert_eq!(cache.position_pdas(&wallet), Some(pdas));
+        assert_eq!(cache.position_pdas(&Pubkey::new_unique()), None);
+    }
+}
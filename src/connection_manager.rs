@@ -0,0 +1,91 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// RPC endpoints to fail over between, the first one being the primary
+///
+/// All requests go to the active endpoint. When one fails with a timeout, connection or server error it moves on to
+/// the next endpoint, round robin, and returns to the primary once it was failed over for `failback_after`.
+pub(crate) struct ConnectionManager {
+    endpoints: Vec<String>,
+    failback_after: Option<Duration>,
+    active: Mutex<ActiveEndpoint>,
+}
+
+struct ActiveEndpoint {
+    index: usize,
+    since: Instant,
+}
+
+impl ConnectionManager {
+    pub(crate) fn new(endpoints: Vec<String>, failback_after: Option<Duration>) -> Self {
+        assert!(!endpoints.is_empty(), "no RPC endpoint");
+        Self { endpoints, failback_after, active: Mutex::new(ActiveEndpoint { index: 0, since: Instant::now() }) }
+    }
+
+    /// The index and URL of the endpoint to send the next request to
+    pub(crate) fn endpoint(&self) -> (usize, &str) {
+        let mut active = self.active.lock().unwrap();
+        if let Some(failback_after) = self.failback_after {
+            if active.index != 0 && active.since.elapsed() >= failback_after {
+                log::info!("returning to primary RPC endpoint {}", self.endpoints[0]);
+                *active = ActiveEndpoint { index: 0, since: Instant::now() };
+            }
+        }
+        (active.index, &self.endpoints[active.index])
+    }
+
+    /// Move on from endpoint `index` after a request to it failed
+    ///
+    /// # Returns
+    /// - `bool` - Whether the next request goes to another endpoint, which is also the case if a concurrent request
+    ///   failed over already
+    pub(crate) fn fail_over(&self, index: usize) -> bool {
+        let mut active = self.active.lock().unwrap();
+        if active.index != index {
+            return true;
+        }
+        if self.endpoints.len() == 1 {
+            return false;
+        }
+        let next = (index + 1) % self.endpoints.len();
+        log::warn!("RPC endpoint {} failed, failing over to {}", self.endpoints[index], self.endpoints[next]);
+        *active = ActiveEndpoint { index: next, since: Instant::now() };
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn endpoints() -> Vec<String> {
+        vec!["https://primary.example.com".to_string(), "https://fallback.example.com".to_string()]
+    }
+
+    #[test]
+    fn test_fail_over() {
+        let manager = ConnectionManager::new(endpoints(), None);
+        assert_eq!(manager.endpoint(), (0, "https://primary.example.com"));
+        assert!(manager.fail_over(0));
+        assert_eq!(manager.endpoint(), (1, "https://fallback.example.com"));
+        // A late failure of a request to the primary does not move on again
+        assert!(manager.fail_over(0));
+        assert_eq!(manager.endpoint().0, 1);
+        assert!(manager.fail_over(1));
+        assert_eq!(manager.endpoint().0, 0);
+    }
+
+    #[test]
+    fn test_fail_back() {
+        let manager = ConnectionManager::new(endpoints(), Some(Duration::ZERO));
+        manager.fail_over(0);
+        assert_eq!(manager.endpoint().0, 0);
+    }
+
+    #[test]
+    fn test_single_endpoint() {
+        let manager = ConnectionManager::new(vec!["https://primary.example.com".to_string()], None);
+        assert!(!manager.fail_over(0));
+        assert_eq!(manager.endpoint().0, 0);
+    }
+}
@@ -0,0 +1,232 @@
+use std::io::{self, Stdout};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::ExecutableCommand;
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Table};
+use ratatui::{Frame, Terminal};
+use raydium_balance_fetcher::{Amount, BalanceFetcher, PoolId, WalletReport};
+use solana_sdk::pubkey::Pubkey;
+
+type Result<T> = anyhow::Result<T>;
+
+/// How long to wait for a key press before redrawing, e.g. to update the time since the last refresh
+const INPUT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Column of the wallets table the wallets are sorted by
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SortColumn {
+    Address,
+    Sol,
+    Wsol,
+    SolUnified,
+    LpSol,
+}
+
+impl SortColumn {
+    fn next(self) -> Self {
+        match self {
+            SortColumn::Address => SortColumn::Sol,
+            SortColumn::Sol => SortColumn::Wsol,
+            SortColumn::Wsol => SortColumn::SolUnified,
+            SortColumn::SolUnified => SortColumn::LpSol,
+            SortColumn::LpSol => SortColumn::Address,
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            SortColumn::Address => "Address",
+            SortColumn::Sol => "SOL",
+            SortColumn::Wsol => "WSOL",
+            SortColumn::SolUnified => "SOL Unified",
+            SortColumn::LpSol => "LP SOL",
+        }
+    }
+}
+
+struct App {
+    reports: Vec<WalletReport>,
+    updated_at: Option<Instant>,
+    error: Option<String>,
+    sort: SortColumn,
+    descending: bool,
+}
+
+impl App {
+    /// The reports in the selected order
+    fn sorted_reports(&self) -> Vec<&WalletReport> {
+        let mut reports: Vec<&WalletReport> = self.reports.iter().collect();
+        match self.sort {
+            SortColumn::Address => reports.sort_by_key(|r| r.address.to_string()),
+            SortColumn::Sol => reports.sort_by_key(|r| r.sol.amount),
+            SortColumn::Wsol => reports.sort_by_key(|r| r.wsol.amount),
+            SortColumn::SolUnified => reports.sort_by_key(|r| r.sol_unified.amount),
+            // A total that overflows sorts first, and is shown as such
+            SortColumn::LpSol => reports.sort_by_key(|r| r.total_amount_0().map(|amount| amount.amount).ok()),
+        }
+        if self.descending {
+            reports.reverse();
+        }
+        reports
+    }
+}
+
+/// Show a dashboard of the balances and SOL-USDC.1bp positions of the wallets, refreshed every `interval`
+///
+/// Balances are fetched in a background thread so that the dashboard stays responsive during slow requests.
+pub(crate) fn run(balance_fetcher: BalanceFetcher, wallets: Vec<Pubkey>, interval: Duration) -> Result<()> {
+    let reports = spawn_fetcher(balance_fetcher, wallets, interval)?;
+
+    enable_raw_mode()?;
+    io::stdout().execute(EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    let result = event_loop(&mut terminal, &reports);
+    // Restore the terminal even if the dashboard failed, so the error can be read
+    disable_raw_mode()?;
+    io::stdout().execute(LeaveAlternateScreen)?;
+    result
+}
+
+fn spawn_fetcher(
+    balance_fetcher: BalanceFetcher,
+    wallets: Vec<Pubkey>,
+    interval: Duration,
+) -> Result<Receiver<Result<Vec<WalletReport>>>> {
+    let pool_id = PoolId::sol_usdc_1bp();
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        // Stop once the dashboard is closed and drops the receiver
+        while sender.send(balance_fetcher.wallet_reports(&wallets, &pool_id).map_err(Into::into)).is_ok() {
+            thread::sleep(interval);
+        }
+    });
+    Ok(receiver)
+}
+
+fn event_loop(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    reports: &Receiver<Result<Vec<WalletReport>>>,
+) -> Result<()> {
+    let mut app = App {
+        reports: vec![],
+        updated_at: None,
+        error: None,
+        sort: SortColumn::SolUnified,
+        descending: true,
+    };
+    loop {
+        while let Ok(result) = reports.try_recv() {
+            match result {
+                Ok(reports) => {
+                    app.reports = reports;
+                    app.updated_at = Some(Instant::now());
+                    app.error = None;
+                }
+                Err(err) => app.error = Some(format!("{:#}", err)),
+            }
+        }
+        terminal.draw(|frame| draw(frame, &app))?;
+
+        if !event::poll(INPUT_POLL_INTERVAL)? {
+            continue;
+        }
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Char('s') => app.sort = app.sort.next(),
+                KeyCode::Char('r') => app.descending = !app.descending,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Show a total of positions, which can only fail by overflowing
+fn total_string(total: raydium_balance_fetcher::Result<Amount>) -> String {
+    total.map_or_else(|_| "overflow".to_string(), |total| total.ui_amount_string())
+}
+
+fn draw(frame: &mut Frame, app: &App) {
+    let areas = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(40), Constraint::Min(0), Constraint::Length(1)])
+        .split(frame.size());
+    let reports = app.sorted_reports();
+    let header_style = Style::default().add_modifier(Modifier::BOLD);
+
+    let wallet_rows = reports.iter().map(|r| {
+        Row::new(vec![
+            r.address.to_string(),
+            r.sol.ui_amount_string(),
+            r.wsol.ui_amount_string(),
+            r.sol_unified.ui_amount_string(),
+            total_string(r.total_amount_0()),
+            total_string(r.total_amount_1()),
+            r.positions.len().to_string(),
+        ])
+    });
+    let wallets = Table::new(wallet_rows, [
+        Constraint::Length(44),
+        Constraint::Length(16),
+        Constraint::Length(16),
+        Constraint::Length(16),
+        Constraint::Length(16),
+        Constraint::Length(16),
+        Constraint::Length(9),
+    ])
+    .header(
+        Row::new(vec!["Address", "SOL", "WSOL", "SOL Unified", "LP SOL", "LP USDC", "Positions"]).style(header_style),
+    )
+    .block(Block::default().borders(Borders::ALL).title("Wallets"));
+    frame.render_widget(wallets, areas[0]);
+
+    let position_rows = reports.iter().flat_map(|r| {
+        r.positions.iter().map(|p| {
+            let (status, color) = if p.in_range { ("In range", Color::Green) } else { ("Out of range", Color::Red) };
+            Row::new(vec![
+                Cell::from(r.address.to_string()),
+                Cell::from(p.position.to_string()),
+                Cell::from(format!("[{}, {})", p.tick_lower_index, p.tick_upper_index)),
+                Cell::from(p.amount_0.ui_amount_string()),
+                Cell::from(p.amount_1.ui_amount_string()),
+                Cell::from(status).style(Style::default().fg(color)),
+            ])
+        })
+    });
+    let positions = Table::new(position_rows, [
+        Constraint::Length(44),
+        Constraint::Length(44),
+        Constraint::Length(18),
+        Constraint::Length(16),
+        Constraint::Length(16),
+        Constraint::Length(12),
+    ])
+    .header(Row::new(vec!["Wallet", "Position", "Tick Range", "SOL", "USDC", "Status"]).style(header_style))
+    .block(Block::default().borders(Borders::ALL).title("SOL-USDC.1bp Positions"));
+    frame.render_widget(positions, areas[1]);
+
+    let updated = match app.updated_at {
+        Some(updated_at) => format!("updated {}s ago", updated_at.elapsed().as_secs()),
+        None => "loading...".to_string(),
+    };
+    let order = if app.descending { "desc" } else { "asc" };
+    let status = match &app.error {
+        Some(err) => Paragraph::new(format!("Error: {}", err)).style(Style::default().fg(Color::Red)),
+        None => Paragraph::new(format!(
+            "{} | sorted by {} ({}) | s: sort, r: reverse, q: quit",
+            updated,
+            app.sort.title(),
+            order
+        )),
+    };
+    frame.render_widget(status, areas[2]);
+}
@@ -0,0 +1,82 @@
+use std::num::{ParseFloatError, ParseIntError};
+use solana_rpc_client_api::client_error::Error as ClientError;
+use solana_sdk::program_error::ProgramError;
+use solana_sdk::pubkey::{ParsePubkeyError, Pubkey};
+use solana_sdk::signature::ParseSignatureError;
+
+pub type Result<T> = std::result::Result<T, BalanceFetcherError>;
+
+/// Error returned by the public APIs of this crate
+#[derive(Debug, thiserror::Error)]
+pub enum BalanceFetcherError {
+    /// The RPC request failed, e.g. a transport error or an error response of the RPC node
+    #[error("RPC error: {0}")]
+    Rpc(Box<ClientError>),
+    /// An account expected to exist is not on chain
+    #[error("account {0} does not exist")]
+    MissingAccount(Pubkey),
+    /// Account data or an RPC response cannot be parsed
+    #[error("deserialization error: {0}")]
+    Deserialize(String),
+    /// CLMM math failed, e.g. a tick out of range or an overflow
+    #[error("math error: {0}")]
+    Math(String),
+    /// An argument is invalid, e.g. a mint that is not a token of the pool
+    #[error("invalid input: {0}")]
+    InvalidInput(String),
+    /// A price is too old to value holdings at
+    #[error("stale price: {0}")]
+    StalePrice(String),
+    /// A cancellable operation was cancelled
+    #[error("operation cancelled")]
+    Cancelled,
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+}
+
+impl From<ClientError> for BalanceFetcherError {
+    fn from(err: ClientError) -> Self {
+        // Boxed as client errors are large, which would make every `Result` of the crate large
+        BalanceFetcherError::Rpc(Box::new(err))
+    }
+}
+
+/// Errors of the Raydium CLMM libraries, which are anchor errors of its math
+impl From<anchor_lang::error::Error> for BalanceFetcherError {
+    fn from(err: anchor_lang::error::Error) -> Self {
+        BalanceFetcherError::Math(err.to_string())
+    }
+}
+
+/// Errors unpacking token program accounts
+impl From<ProgramError> for BalanceFetcherError {
+    fn from(err: ProgramError) -> Self {
+        BalanceFetcherError::Deserialize(err.to_string())
+    }
+}
+
+impl From<ParsePubkeyError> for BalanceFetcherError {
+    fn from(err: ParsePubkeyError) -> Self {
+        BalanceFetcherError::Deserialize(err.to_string())
+    }
+}
+
+impl From<ParseSignatureError> for BalanceFetcherError {
+    fn from(err: ParseSignatureError) -> Self {
+        BalanceFetcherError::Deserialize(err.to_string())
+    }
+}
+
+impl From<ParseIntError> for BalanceFetcherError {
+    fn from(err: ParseIntError) -> Self {
+        BalanceFetcherError::Deserialize(err.to_string())
+    }
+}
+
+impl From<ParseFloatError> for BalanceFetcherError {
+    fn from(err: ParseFloatError) -> Self {
+        BalanceFetcherError::Deserialize(err.to_string())
+    }
+}
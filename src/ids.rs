@@ -0,0 +1,159 @@
+use std::fmt;
+use std::ops::Deref;
+use std::str::FromStr;
+use solana_sdk::pubkey::Pubkey;
+use crate::balance_fetcher::{SOL_USDC_1BP_POOL_ID, USDC_MINT_ADDRESS, WSOL_MINT_ADDRESS};
+use crate::error::{BalanceFetcherError, Result};
+
+/// Raydium CLMM pools known by name, by pool ID
+const KNOWN_POOLS: &[(&str, &str)] = &[(SOL_USDC_1BP_POOL_ID, "SOL-USDC.1bp")];
+
+/// The ID of a Raydium CLMM pool
+///
+/// Pool IDs are program derived addresses, which are off the ed25519 curve, while wallet addresses are on it. So a
+/// wallet address passed where a pool ID belongs is rejected when parsed, instead of finding no position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PoolId(Pubkey);
+
+impl PoolId {
+    /// Fail with `BalanceFetcherError::InvalidInput` if `pubkey` cannot be a pool ID, being on the ed25519 curve
+    pub fn new(pubkey: Pubkey) -> Result<Self> {
+        if pubkey.is_on_curve() {
+            return Err(BalanceFetcherError::InvalidInput(format!(
+                "{} is not a pool ID but a wallet or mint address",
+                pubkey
+            )));
+        }
+        Ok(Self(pubkey))
+    }
+
+    /// The SOL-USDC.1bp pool on mainnet
+    pub fn sol_usdc_1bp() -> Self {
+        Self(Pubkey::from_str(SOL_USDC_1BP_POOL_ID).unwrap())
+    }
+
+    pub fn pubkey(&self) -> Pubkey {
+        self.0
+    }
+
+    /// The name of the pool if it is a well-known one, e.g. "SOL-USDC.1bp"
+    pub fn known_name(&self) -> Option<&'static str> {
+        let pool_id = self.0.to_string();
+        KNOWN_POOLS.iter().find(|(known_pool_id, _)| *known_pool_id == pool_id).map(|(_, name)| *name)
+    }
+}
+
+impl FromStr for PoolId {
+    type Err = BalanceFetcherError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let pubkey =
+            Pubkey::from_str(s).map_err(|_| BalanceFetcherError::InvalidInput(format!("invalid pool ID {:?}", s)))?;
+        Self::new(pubkey)
+    }
+}
+
+/// The address of an SPL token mint, of the Token or Token-2022 program
+///
+/// Parsing only checks the address, whether it is a mint is checked when its account is fetched.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MintAddress(Pubkey);
+
+impl MintAddress {
+    /// The mint of WSOL (Wrapped SOL)
+    pub fn wsol() -> Self {
+        Self(Pubkey::from_str(WSOL_MINT_ADDRESS).unwrap())
+    }
+
+    /// The mint of USDC on mainnet
+    pub fn usdc() -> Self {
+        Self(Pubkey::from_str(USDC_MINT_ADDRESS).unwrap())
+    }
+
+    pub fn pubkey(&self) -> Pubkey {
+        self.0
+    }
+}
+
+impl From<Pubkey> for MintAddress {
+    fn from(pubkey: Pubkey) -> Self {
+        Self(pubkey)
+    }
+}
+
+impl FromStr for MintAddress {
+    type Err = BalanceFetcherError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match Pubkey::from_str(s) {
+            // The default address is the system program, which no token program would take as a mint
+            Ok(pubkey) if pubkey != Pubkey::default() => Ok(Self(pubkey)),
+            _ => Err(BalanceFetcherError::InvalidInput(format!("invalid mint address {:?}", s))),
+        }
+    }
+}
+
+impl Deref for PoolId {
+    type Target = Pubkey;
+
+    fn deref(&self) -> &Pubkey {
+        &self.0
+    }
+}
+
+impl From<PoolId> for Pubkey {
+    fn from(pool_id: PoolId) -> Self {
+        pool_id.0
+    }
+}
+
+impl fmt::Display for PoolId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Deref for MintAddress {
+    type Target = Pubkey;
+
+    fn deref(&self) -> &Pubkey {
+        &self.0
+    }
+}
+
+impl From<MintAddress> for Pubkey {
+    fn from(mint_address: MintAddress) -> Self {
+        mint_address.0
+    }
+}
+
+impl fmt::Display for MintAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_id() {
+        let pool_id = PoolId::from_str(SOL_USDC_1BP_POOL_ID).unwrap();
+        assert_eq!(pool_id, PoolId::sol_usdc_1bp());
+        assert_eq!(pool_id.known_name(), Some("SOL-USDC.1bp"));
+        assert_eq!(pool_id.to_string(), SOL_USDC_1BP_POOL_ID);
+        // A wallet address in place of a pool ID
+        let wallet = "53zSj4G935ZY2a5x2UnGAiJXSuXXmGHaLph2zhAUvYpg";
+        assert!(matches!(PoolId::from_str(wallet), Err(BalanceFetcherError::InvalidInput(_))));
+        assert!(matches!(PoolId::from_str("not a pool"), Err(BalanceFetcherError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_mint_address() {
+        assert_eq!(MintAddress::from_str(WSOL_MINT_ADDRESS).unwrap(), MintAddress::wsol());
+        assert_eq!(MintAddress::usdc().pubkey(), Pubkey::from_str(USDC_MINT_ADDRESS).unwrap());
+        assert!(MintAddress::from_str("11111111111111111111111111111111").is_err());
+        assert!(MintAddress::from_str("not a mint").is_err());
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+use crate::error::Result;
+use crate::ids::MintAddress;
+use crate::pricing::{PriceSource, TokenPrice};
+
+pub const JUPITER_QUOTE_API_URL: &str = "https://lite-api.jup.ag/swap/v1/quote";
+
+pub const JUPITER_PRICE_API_URL: &str = "https://lite-api.jup.ag/price/v3";
+
+/// Most mints the price API takes in one request
+const MAX_PRICE_IDS: usize = 50;
+
+/// A quote of swapping through the best route found by Jupiter
+#[derive(Clone, Debug, PartialEq)]
+pub struct JupiterQuote {
+    pub in_amount: u64,
+    pub out_amount: u64,
+    /// Price impact of the route, e.g. 0.01 is 1%
+    pub price_impact: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct QuoteResponse {
+    in_amount: String,
+    out_amount: String,
+    price_impact_pct: String,
+}
+
+/// A price of the price API, which leaves out the mints it cannot price
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PriceData {
+    usd_price: f64,
+}
+
+/// A blocking client of the Jupiter quote API with a quote cache and a minimum interval between requests
+pub struct JupiterClient {
+    http: reqwest::blocking::Client,
+    url: String,
+    min_interval: Duration,
+    cache_ttl: Duration,
+    last_request: Mutex<Option<Instant>>,
+    cache: Mutex<HashMap<(Pubkey, Pubkey, u64), (Instant, JupiterQuote)>>,
+}
+
+impl Default for JupiterClient {
+    /// Use the public quote API, at most 1 request per second and quotes cached for 30 seconds
+    fn default() -> Self {
+        Self::new(JUPITER_QUOTE_API_URL, Duration::from_secs(1), Duration::from_secs(30))
+    }
+}
+
+impl JupiterClient {
+    pub fn new<T: ToString>(url: T, min_interval: Duration, cache_ttl: Duration) -> Self {
+        Self {
+            http: reqwest::blocking::Client::new(),
+            url: url.to_string(),
+            min_interval,
+            cache_ttl,
+            last_request: Mutex::new(None),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Quote swapping an exact amount of input token into output token
+    ///
+    /// # Arguments
+    /// - `input_mint` - The mint of the input token
+    /// - `output_mint` - The mint of the output token
+    /// - `amount` - Amount of input token in raw units
+    ///
+    /// # Returns
+    /// - `JupiterQuote` - The output amount and price impact of the best route, possibly from cache
+    pub fn quote(&self, input_mint: &Pubkey, output_mint: &Pubkey, amount: u64) -> Result<JupiterQuote> {
+        let key = (*input_mint, *output_mint, amount);
+        if let Some((fetched_at, quote)) = self.cache.lock().unwrap().get(&key) {
+            if fetched_at.elapsed() < self.cache_ttl {
+                return Ok(quote.clone());
+            }
+        }
+
+        self.wait_for_rate_limit();
+        let rsp: QuoteResponse = self.http
+            .get(&self.url)
+            .query(&[
+                ("inputMint", input_mint.to_string()),
+                ("outputMint", output_mint.to_string()),
+                ("amount", amount.to_string()),
+            ])
+            .send()?
+            .error_for_status()?
+            .json()?;
+        let quote = JupiterQuote {
+            in_amount: u64::from_str(&rsp.in_amount)?,
+            out_amount: u64::from_str(&rsp.out_amount)?,
+            price_impact: f64::from_str(&rsp.price_impact_pct)?,
+        };
+        let mut cache = self.cache.lock().unwrap();
+        // Quotes of other amounts are rarely asked for again, so expired ones are dropped rather than replaced
+        cache.retain(|_, (fetched_at, _)| fetched_at.elapsed() < self.cache_ttl);
+        cache.insert(key, (Instant::now(), quote.clone()));
+        Ok(quote)
+    }
+
+    fn wait_for_rate_limit(&self) {
+        wait_for_min_interval(&self.last_request, self.min_interval);
+    }
+}
+
+/// Prices of mints from the Jupiter price API, derived from the routes Jupiter swaps through
+///
+/// It prices long-tail tokens no oracle has a feed of, as long as they trade somewhere Jupiter routes through.
+/// Prices, and mints the API cannot price, are cached, and requests are spaced by a minimum interval.
+pub struct JupiterPriceSource {
+    http: reqwest::blocking::Client,
+    url: String,
+    min_interval: Duration,
+    cache_ttl: Duration,
+    last_request: Mutex<Option<Instant>>,
+    cache: Mutex<HashMap<MintAddress, (Instant, Option<TokenPrice>)>>,
+}
+
+impl Default for JupiterPriceSource {
+    /// Use the public price API, at most 1 request per second and prices cached for 30 seconds
+    fn default() -> Self {
+        Self::new(JUPITER_PRICE_API_URL, Duration::from_secs(1), Duration::from_secs(30))
+    }
+}
+
+impl JupiterPriceSource {
+    pub fn new<T: ToString>(url: T, min_interval: Duration, cache_ttl: Duration) -> Self {
+        Self {
+            http: reqwest::blocking::Client::new(),
+            url: url.to_string(),
+            min_interval,
+            cache_ttl,
+            last_request: Mutex::new(None),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn cached_price(&self, mint: &MintAddress) -> Option<Option<TokenPrice>> {
+        let cache = self.cache.lock().unwrap();
+        let (fetched_at, price) = cache.get(mint)?;
+        (fetched_at.elapsed() < self.cache_ttl).then_some(*price)
+    }
+}
+
+impl PriceSource for JupiterPriceSource {
+    fn usd_prices(&self, mints: &[MintAddress]) -> Result<Vec<Option<TokenPrice>>> {
+        let mut uncached: Vec<MintAddress> =
+            mints.iter().filter(|mint| self.cached_price(mint).is_none()).copied().collect();
+        uncached.sort();
+        uncached.dedup();
+        for chunk in uncached.chunks(MAX_PRICE_IDS) {
+            wait_for_min_interval(&self.last_request, self.min_interval);
+            let ids: Vec<String> = chunk.iter().map(MintAddress::to_string).collect();
+            let rsp: HashMap<String, PriceData> = self.http
+                .get(&self.url)
+                .query(&[("ids", ids.join(","))])
+                .send()?
+                .error_for_status()?
+                .json()?;
+            let mut cache = self.cache.lock().unwrap();
+            for (mint, id) in chunk.iter().zip(&ids) {
+                let price =
+                    rsp.get(id).map(|data| TokenPrice { price: data.usd_price, confidence: None, publish_time: None });
+                cache.insert(*mint, (Instant::now(), price));
+            }
+        }
+        let cache = self.cache.lock().unwrap();
+        // Prices of the request are used even if the cache TTL is zero
+        Ok(mints.iter().map(|mint| cache.get(mint).and_then(|(_, price)| *price)).collect())
+    }
+}
+
+/// Sleep until `min_interval` has passed since the last request, and take the next request's turn
+fn wait_for_min_interval(last_request: &Mutex<Option<Instant>>, min_interval: Duration) {
+    let mut last_request = last_request.lock().unwrap();
+    if let Some(last) = *last_request {
+        let elapsed = last.elapsed();
+        if elapsed < min_interval {
+            std::thread::sleep(min_interval - elapsed);
+        }
+    }
+    *last_request = Some(Instant::now());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        let jupiter = JupiterClient::default();
+        let wsol = Pubkey::from_str("So11111111111111111111111111111111111111112").unwrap();
+        let usdc = Pubkey::from_str("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v").unwrap();
+        let quote = jupiter.quote(&wsol, &usdc, 1_000_000_000).unwrap();
+        assert_eq!(quote.in_amount, 1_000_000_000);
+        assert!(quote.out_amount > 0);
+        // The second quote is served from cache
+        assert_eq!(jupiter.quote(&wsol, &usdc, 1_000_000_000).unwrap(), quote);
+    }
+
+    #[test]
+    fn test_usd_prices() {
+        let jupiter = JupiterPriceSource::default();
+        let unknown = MintAddress::from(Pubkey::new_unique());
+        let prices = jupiter.usd_prices(&[MintAddress::wsol(), unknown, MintAddress::usdc()]).unwrap();
+        assert!(prices[0].unwrap().price > 0.0);
+        assert_eq!(prices[1], None);
+        assert!((prices[2].unwrap().price - 1.0).abs() < 0.05);
+        // The second price is served from cache
+        assert_eq!(jupiter.usd_price(&MintAddress::wsol()).unwrap(), prices[0]);
+    }
+}
@@ -0,0 +1,27 @@
+use anyhow::anyhow;
+use solana_remote_wallet::locator::{Locator, Manufacturer};
+use solana_remote_wallet::remote_keypair::generate_remote_keypair;
+use solana_remote_wallet::remote_wallet::maybe_wallet_manager;
+use solana_sdk::derivation_path::DerivationPath;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signer::Signer;
+
+type Result<T> = anyhow::Result<T>;
+
+/// Read the address of an account of the Solana app on a connected Ledger, without signing anything
+///
+/// # Arguments
+/// - `account` - The account index of the derivation path m/44'/501'/account', or m/44'/501' if `None`, as the Solana
+///   CLI derives for `usb://ledger`
+///
+/// # Returns
+/// - `Pubkey` - The address of the account
+pub(crate) fn ledger_address(account: Option<u32>) -> Result<Pubkey> {
+    let wallet_manager = maybe_wallet_manager()?
+        .ok_or_else(|| anyhow!("no Ledger found, connect it, unlock it and open the Solana app"))?;
+    let locator = Locator { manufacturer: Manufacturer::Ledger, pubkey: None };
+    let derivation_path = DerivationPath::new_bip44(account, None);
+    // The address is only read, so there is nothing to confirm on the device
+    let keypair = generate_remote_keypair(locator, derivation_path, &wallet_manager, false, "ledger")?;
+    Ok(keypair.pubkey())
+}
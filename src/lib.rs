@@ -0,0 +1,36 @@
+//! Fetch SOL, SPL token and Raydium CLMM LP position balances of Solana wallets
+
+pub mod account_view;
+pub mod async_balance_fetcher;
+pub mod balance_fetcher;
+pub mod builder;
+pub mod cache;
+mod connection_manager;
+pub mod error;
+pub mod ids;
+#[cfg(feature = "jupiter")]
+pub mod jupiter;
+pub mod math;
+pub mod pricing;
+mod rate_limiter;
+pub mod report;
+pub mod rpc_sender;
+pub mod swap_quote;
+pub mod token_metadata;
+pub mod valuation;
+
+pub use async_balance_fetcher::AsyncBalanceFetcher;
+pub use balance_fetcher::*;
+pub use builder::BalanceFetcherBuilder;
+pub use cache::CacheTtls;
+pub use error::{BalanceFetcherError, Result};
+pub use ids::{MintAddress, PoolId};
+#[cfg(feature = "jupiter")]
+pub use jupiter::{JupiterClient, JupiterPriceSource, JupiterQuote};
+pub use math::{PendingReward, PositionAmounts};
+pub use pricing::{PriceSource, PythPriceSource, TokenPrice};
+pub use report::{Amount, AssetClass, PositionReport, WalletReport};
+pub use rpc_sender::RetryPolicy;
+pub use swap_quote::{SwapDirection, SwapQuote};
+pub use token_metadata::TokenMetadata;
+pub use valuation::{UsdPrices, WalletValue};
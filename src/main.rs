@@ -1,50 +1,805 @@
+use std::collections::BTreeSet;
 use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::path::PathBuf;
 use std::str::FromStr;
-use balance_fetcher::BalanceFetcher;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use anyhow::anyhow;
+use clap::{Parser, Subcommand, ValueEnum};
+use indicatif::{ProgressBar, ProgressStyle};
+use raydium_balance_fetcher::report::{self, Amount, AmountStats, CombinedReport, StatsReport, WalletReport, CSV_HEADER};
+use raydium_balance_fetcher::{
+    AddressKind, BalanceFetcher, CacheTtls, MintAddress, PoolId, PythPriceSource, TokenAccountInfo, UsdPrices,
+    WalletValue,
+};
+use serde::Serialize;
 use solana_sdk::pubkey::Pubkey;
+#[cfg(feature = "jupiter")]
+use raydium_balance_fetcher::JupiterPriceSource;
 
-mod balance_fetcher;
+#[cfg(feature = "dashboard")]
+mod dashboard;
+#[cfg(feature = "ledger")]
+mod ledger;
+mod prompt;
 
 type Result<T> = anyhow::Result<T>;
 
+const WATCH_SLEEP_STEP: Duration = Duration::from_millis(200);
+
+const DEFAULT_RPC_URL: &str = "https://api.mainnet-beta.solana.com";
+
+/// Get SOL/WSOL balances and Raydium SOL-USDC.1bp LP positions of Solana mainnet addresses
+///
+/// Every option can also be set by an `RBF_` environment variable. If no address is given on the command line
+/// or in a file, they are read from `RBF_WALLETS`, separated by commas or whitespace, or else from stdin. On a
+/// terminal, an address is asked for instead, offering recently entered ones.
+#[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    /// The wallet or token account addresses, e.g. 53zSj4G935ZY2a5x2UnGAiJXSuXXmGHaLph2zhAUvYpg
+    #[arg(value_parser = parse_address)]
+    addresses: Vec<Pubkey>,
+
+    /// Read more addresses from this file, one per line, or from stdin if it is `-`
+    ///
+    /// Addresses are also read from stdin when none is given and stdin is not a terminal.
+    #[arg(long, env = "RBF_ADDRESSES_FILE")]
+    addresses_file: Option<PathBuf>,
+
+    /// Also summarize the address of the Solana app on a connected Ledger
+    #[cfg(feature = "ledger")]
+    #[arg(long, env = "RBF_LEDGER")]
+    ledger: bool,
+
+    /// The account index of the Ledger address, m/44'/501'/N', instead of m/44'/501'
+    #[cfg(feature = "ledger")]
+    #[arg(long, env = "RBF_LEDGER_ACCOUNT", requires = "ledger")]
+    ledger_account: Option<u32>,
+
+    /// The format to print the summary of the addresses in
+    #[arg(long, env = "RBF_OUTPUT", value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+
+    /// Write the summary of the addresses to this file instead of stdout
+    #[arg(long, env = "RBF_OUT_FILE")]
+    out_file: Option<PathBuf>,
+
+    /// Keep re-fetching and printing the summary until Ctrl-C is pressed
+    #[arg(long, env = "RBF_WATCH")]
+    watch: bool,
+
+    /// How often to re-fetch in watch mode, e.g. 30s or 5m
+    #[arg(long, env = "RBF_INTERVAL", value_parser = humantime::parse_duration, default_value = "30s")]
+    interval: Duration,
+
+    /// In watch mode, only print the balances that changed after the first summary
+    #[arg(long, env = "RBF_DELTAS_ONLY")]
+    deltas_only: bool,
+
+    /// Also copy the summary to the clipboard, in the format of `--output`
+    #[cfg(feature = "clipboard")]
+    #[arg(long, env = "RBF_COPY", conflicts_with = "watch")]
+    copy: bool,
+
+    /// Only summarize the N largest wallets by `--by`
+    #[arg(long, env = "RBF_TOP", value_parser = clap::value_parser!(u64).range(1..))]
+    top: Option<u64>,
+
+    /// The balance `--top` ranks wallets by
+    #[arg(long, env = "RBF_BY", value_enum, default_value_t = Metric::SolUnified)]
+    by: Metric,
+
+    /// Also value the balances and LP positions, by the current prices of Raydium pools, in text and JSON output
+    #[arg(long, env = "RBF_VALUE_IN", value_enum)]
+    value_in: Option<Currency>,
+
+    /// Where `--value-in` takes prices from
+    #[arg(long, env = "RBF_PRICE_SOURCE", value_enum, default_value_t = Pricing::Pool, requires = "value_in")]
+    price_source: Pricing,
+
+    /// More mints for `--price-source pyth` to price, as MINT=PRICE_ACCOUNT pairs of Pyth price feed accounts
+    #[arg(long = "pyth-feed", env = "RBF_PYTH_FEEDS", value_delimiter = ',', value_parser = parse_pyth_feed)]
+    pyth_feeds: Vec<(MintAddress, Pubkey)>,
+
+    /// The RPC endpoint to query, `SOLANA_RPC_URL` is also read if `RBF_RPC_URL` is not set
+    /// [default: https://api.mainnet-beta.solana.com]
+    #[arg(long, global = true, env = "RBF_RPC_URL")]
+    rpc_url: Option<String>,
+
+    /// RPC endpoints to fail over to, in order, when the previous one times out or fails with a server error
+    #[arg(long = "fallback-rpc-url", global = true, env = "RBF_FALLBACK_RPC_URLS", value_delimiter = ',')]
+    fallback_rpc_urls: Vec<String>,
+
+    /// Return to the first RPC endpoint after having failed over for this long, e.g. 5m
+    #[arg(long, global = true, env = "RBF_FAILBACK_AFTER", value_parser = humantime::parse_duration)]
+    failback_after: Option<Duration>,
+
+    /// Send at most this many RPC requests per second, e.g. 5 for the throttled public mainnet endpoint
+    #[arg(long, global = true, env = "RBF_RATE_LIMIT")]
+    rate_limit: Option<f64>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Human-readable summary
+    Text,
+    /// Structured JSON for scripting
+    Json,
+    /// One row per asset, for spreadsheets
+    Csv,
+    /// Distribution of the balances and position amounts over the wallets
+    Stats,
+}
+
+/// A balance of a wallet to rank wallets by
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[value(rename_all = "snake_case")]
+enum Metric {
+    Sol,
+    Wsol,
+    SolUnified,
+    /// SOL in SOL-USDC.1bp LP positions
+    LpSol,
+    /// USDC in SOL-USDC.1bp LP positions
+    LpUsdc,
+}
+
+impl Metric {
+    fn amount(self, report: &WalletReport) -> Result<u64> {
+        Ok(match self {
+            Metric::Sol => report.sol.amount,
+            Metric::Wsol => report.wsol.amount,
+            Metric::SolUnified => report.sol_unified.amount,
+            Metric::LpSol => report.total_amount_0()?.amount,
+            Metric::LpUsdc => report.total_amount_1()?.amount,
+        })
+    }
+}
+
+/// A currency to value wallets in
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Currency {
+    /// US dollars, priced as USDC
+    Usd,
+}
+
+/// A source of the prices to value wallets at
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Pricing {
+    /// The current price of the SOL-USDC.1bp pool
+    Pool,
+    /// Pyth oracle prices of SOL, USDC and `--pyth-feed` mints, left out if they are more than a minute old
+    Pyth,
+    /// The Jupiter price API, which also prices long-tail tokens such as reward tokens
+    #[cfg(feature = "jupiter")]
+    Jupiter,
+}
+
+/// What `Summary::fetch` fetches on top of the reports of the addresses
+#[derive(Clone)]
+struct SummaryOptions {
+    /// Only the `top.0` largest wallets by `top.1`
+    top: Option<(usize, Metric)>,
+    value_in: Option<Currency>,
+    price_source: Pricing,
+    /// Price feed accounts of mints Pyth does not price by default
+    pyth_feeds: Vec<(MintAddress, Pubkey)>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Inspect a Raydium pool
+    Pool {
+        #[command(subcommand)]
+        command: PoolCommand,
+    },
+    /// Value the SOL-USDC.1bp LP positions as if withdrawn and swapped to USDC now
+    ExitValue {
+        #[arg(value_parser = parse_address)]
+        address: Pubkey,
+    },
+    /// Check which mints lack an associated token account, WSOL and USDC if no mint is given
+    Preflight {
+        #[arg(value_parser = parse_address)]
+        address: Pubkey,
+        #[arg(value_parser = parse_mint_address)]
+        mints: Vec<MintAddress>,
+    },
+    /// List the balances of all SPL tokens of a wallet, grouped by mint
+    Tokens {
+        #[arg(value_parser = parse_address)]
+        address: Pubkey,
+    },
+    /// List the Raydium CLMM positions of a wallet in all pools, grouped by pool
+    Positions {
+        #[arg(value_parser = parse_address)]
+        address: Pubkey,
+    },
+    /// Check that the RPC endpoint serves the methods this tool uses, with their latency
+    Doctor,
+    /// Live dashboard of the balances and SOL-USDC.1bp positions of wallets
+    #[cfg(feature = "dashboard")]
+    Dashboard {
+        #[arg(required = true, value_parser = parse_address)]
+        addresses: Vec<Pubkey>,
+        /// How often to refresh, e.g. 30s or 5m
+        #[arg(long, env = "RBF_INTERVAL", value_parser = humantime::parse_duration, default_value = "30s")]
+        interval: Duration,
+    },
+}
+
+#[derive(Subcommand)]
+enum PoolCommand {
+    /// Aggregate statistics of all positions in the pool
+    Stats {
+        #[arg(value_parser = parse_pool_id)]
+        pool_id: PoolId,
+    },
+    /// The current price of the pool, of token 0 in token 1 and the inverse
+    Price {
+        #[arg(value_parser = parse_pool_id)]
+        pool_id: PoolId,
+    },
+}
+
 fn main() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
+    let cli = Cli::parse();
+    let rpc_url = cli
+        .rpc_url
+        .or_else(|| env::var("SOLANA_RPC_URL").ok())
+        .unwrap_or_else(|| DEFAULT_RPC_URL.to_string());
+    // Watch mode and the dashboard refetch the same pool and position PDAs over and over
+    let mut builder = BalanceFetcher::builder(&rpc_url).cache(CacheTtls::default());
+    if let Some(rate_limit) = cli.rate_limit {
+        builder = builder.rate_limit(rate_limit);
+    }
+    for fallback_rpc_url in &cli.fallback_rpc_urls {
+        builder = builder.fallback_rpc_url(fallback_rpc_url);
+    }
+    if let Some(failback_after) = cli.failback_after {
+        builder = builder.failback_after(failback_after);
+    }
+    let balance_fetcher = builder.build()?;
+
+    match cli.command {
+        Some(Command::Pool { command: PoolCommand::Stats { pool_id } }) => {
+            return pool_stats_command(&balance_fetcher, &pool_id);
+        }
+        Some(Command::Pool { command: PoolCommand::Price { pool_id } }) => {
+            return pool_price_command(&balance_fetcher, &pool_id);
+        }
+        Some(Command::ExitValue { address }) => return exit_value_command(&balance_fetcher, &address),
+        Some(Command::Preflight { address, mints }) => return preflight_command(&balance_fetcher, &address, mints),
+        Some(Command::Tokens { address }) => return tokens_command(&balance_fetcher, &address),
+        Some(Command::Positions { address }) => return positions_command(&balance_fetcher, &address),
+        Some(Command::Doctor) => return doctor_command(&balance_fetcher),
+        #[cfg(feature = "dashboard")]
+        Some(Command::Dashboard { addresses, interval }) => return dashboard::run(balance_fetcher, addresses, interval),
+        None => {}
+    }
 
-    if args.len() < 2 {
-        eprintln!("Please Usage: {} <address>", args[0]);
-        eprintln!("Example: {} 53zSj4G935ZY2a5x2UnGAiJXSuXXmGHaLph2zhAUvYpg", args[0]);
+    let mut addresses = cli.addresses;
+    #[cfg(feature = "ledger")]
+    if cli.ledger {
+        addresses.push(ledger::ledger_address(cli.ledger_account)?);
+    }
+    match &cli.addresses_file {
+        Some(path) if path.as_os_str() == "-" => addresses.extend(read_addresses(io::stdin().lock())?),
+        Some(path) => addresses.extend(read_addresses(BufReader::new(File::open(path)?))?),
+        // Containers often run with stdin attached but never written to, so prefer the environment variable
+        None if addresses.is_empty() && env::var_os("RBF_WALLETS").is_some() => {
+            addresses = parse_address_list(&env::var("RBF_WALLETS")?)?;
+        }
+        None if addresses.is_empty() && !io::stdin().is_terminal() => {
+            addresses.extend(read_addresses(io::stdin().lock())?);
+        }
+        // Run without arguments in a terminal, e.g. by someone not used to the command line
+        None if addresses.is_empty() => addresses.push(prompt::prompt_address()?),
+        None => {}
+    }
+    if addresses.is_empty() {
+        eprintln!("Please provide an address. Example: 53zSj4G935ZY2a5x2UnGAiJXSuXXmGHaLph2zhAUvYpg");
         std::process::exit(1);
     }
+    let mut out: Box<dyn Write> = match &cli.out_file {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout()),
+    };
 
-    let rpc_url = "https://api.mainnet-beta.solana.com";
-    let balance_fetcher = BalanceFetcher::new(rpc_url);
+    let options = SummaryOptions {
+        top: cli.top.map(|top| (top as usize, cli.by)),
+        value_in: cli.value_in,
+        price_source: cli.price_source,
+        pyth_feeds: cli.pyth_feeds,
+    };
+    if cli.watch {
+        return watch(&balance_fetcher, &addresses, &options, cli.output, cli.interval, cli.deltas_only, &mut out);
+    }
+    let summary = Summary::fetch(&balance_fetcher, &addresses, &options)?;
+    #[cfg(feature = "clipboard")]
+    if cli.copy {
+        let mut buffer = Vec::new();
+        summary.write(&mut buffer, cli.output)?;
+        out.write_all(&buffer)?;
+        arboard::Clipboard::new()?.set_text(String::from_utf8(buffer)?)?;
+        eprintln!("Copied to the clipboard");
+        return Ok(());
+    }
+    summary.write(&mut out, cli.output)
+}
 
-    let addr = Pubkey::from_str(args[1].as_str())
-        .unwrap_or_else(|_| {
-            eprintln!("Invalid address. Good address example: 53zSj4G935ZY2a5x2UnGAiJXSuXXmGHaLph2zhAUvYpg");
-            std::process::exit(1);
-        });
+/// Re-fetch and print the summary of the addresses every `interval` until Ctrl-C is pressed
+fn watch<W: Write>(
+    balance_fetcher: &BalanceFetcher,
+    addresses: &[Pubkey],
+    options: &SummaryOptions,
+    output: OutputFormat,
+    interval: Duration,
+    deltas_only: bool,
+    out: &mut W,
+) -> Result<()> {
+    let running = Arc::new(AtomicBool::new(true));
+    let handler_running = running.clone();
+    ctrlc::set_handler(move || handler_running.store(false, Ordering::SeqCst))?;
 
-    let balance_sol = balance_fetcher.balance_sol(&addr)?;
-    let balance_wsol = balance_fetcher.balance_wsol(&addr)?;
-    let balance_sol_unified = balance_fetcher.balance_sol_unified(&addr)?;
-    let balance_sol_position = balance_fetcher.position_sol_usdc_1bp(&addr)?.0;
+    let mut previous: Option<Summary> = None;
+    while running.load(Ordering::SeqCst) {
+        // A transient RPC error should not end the watch, the next iteration will retry
+        match Summary::fetch(balance_fetcher, addresses, options) {
+            Ok(summary) => {
+                match &previous {
+                    Some(previous) if deltas_only => summary.write_changes_since(out, previous)?,
+                    _ => summary.write(out, output)?,
+                }
+                out.flush()?;
+                previous = Some(summary);
+            }
+            Err(err) => eprintln!("Failed to fetch balances: {:#}", err),
+        }
 
-    let sol_decimals = 9;
-    let sol_multiplier = 10u64.pow(sol_decimals);
-    let (balance_sol, balance_wsol, balance_sol_unified, balance_sol_position) = (
-        balance_sol as f64 / sol_multiplier as f64,
-        balance_wsol as f64 / sol_multiplier as f64,
-        balance_sol_unified as f64 / sol_multiplier as f64,
-        balance_sol_position as f64 / sol_multiplier as f64,
-    );
+        // Sleep in short steps to react to Ctrl-C promptly
+        let started_at = Instant::now();
+        while running.load(Ordering::SeqCst) && started_at.elapsed() < interval {
+            thread::sleep(WATCH_SLEEP_STEP.min(interval));
+        }
+    }
+    Ok(())
+}
 
-    println!("
+/// The token accounts and wallet reports of the addresses given on the command line
+struct Summary {
+    single_address: bool,
+    token_accounts: Vec<(Pubkey, TokenAccountInfo)>,
+    reports: Vec<WalletReport>,
+    /// The USD values of `reports`, in their order, if asked for
+    values: Option<Vec<WalletValue>>,
+}
+
+impl Summary {
+    /// Fetch the summary of the addresses as set by `options`
+    fn fetch(balance_fetcher: &BalanceFetcher, addresses: &[Pubkey], options: &SummaryOptions) -> Result<Self> {
+        // Token accounts are reported on their own, everything else is summarized as a wallet
+        let mut token_accounts = Vec::new();
+        let mut wallets = Vec::new();
+        for (addr, kind) in addresses.iter().zip(balance_fetcher.classify_addresses(addresses)?) {
+            match kind {
+                AddressKind::TokenAccount(token_account) => token_accounts.push((*addr, token_account)),
+                _ => wallets.push(*addr),
+            }
+        }
+        let pool_id = PoolId::sol_usdc_1bp();
+        let mut reports = if wallets.is_empty() { vec![] } else { balance_fetcher.wallet_reports(&wallets, &pool_id)? };
+        if let Some((top, by)) = options.top {
+            let mut ranked = reports
+                .into_iter()
+                .map(|report| Ok((by.amount(&report)?, report)))
+                .collect::<Result<Vec<_>>>()?;
+            ranked.sort_by_key(|(amount, _)| std::cmp::Reverse(*amount));
+            reports = ranked.into_iter().take(top).map(|(_, report)| report).collect();
+        }
+        let values = match options.value_in {
+            Some(Currency::Usd) => {
+                let mints: BTreeSet<MintAddress> = reports.iter().flat_map(WalletReport::mints).collect();
+                let mints: Vec<MintAddress> = mints.into_iter().collect();
+                let prices = match options.price_source {
+                    Pricing::Pool => balance_fetcher.usd_prices(&[])?,
+                    Pricing::Pyth => {
+                        let mut price_source = PythPriceSource::new(balance_fetcher);
+                        for (mint, price_account) in &options.pyth_feeds {
+                            price_source = price_source.with_price_account(*mint, *price_account);
+                        }
+                        UsdPrices::from_price_source(&price_source, &mints)?
+                    }
+                    #[cfg(feature = "jupiter")]
+                    Pricing::Jupiter => UsdPrices::from_price_source(&JupiterPriceSource::default(), &mints)?,
+                };
+                Some(reports.iter().map(|report| report.usd_value(&prices)).collect())
+            }
+            None => None,
+        };
+        Ok(Self { single_address: addresses.len() == 1, token_accounts, reports, values })
+    }
+
+    fn write<W: Write>(&self, out: &mut W, output: OutputFormat) -> Result<()> {
+        match output {
+            OutputFormat::Json if self.single_address => match self.token_accounts.first() {
+                Some((_, token_account)) => writeln!(out, "{}", serde_json::to_string_pretty(token_account)?)?,
+                None => {
+                    let output = WalletOutput {
+                        report: &self.reports[0],
+                        usd_value: self.values.as_ref().map(|values| &values[0]),
+                    };
+                    writeln!(out, "{}", serde_json::to_string_pretty(&output)?)?
+                }
+            },
+            OutputFormat::Json => {
+                let output = MultiAddressOutput {
+                    combined: CombinedReport::new(self.reports.clone())?,
+                    token_accounts: self
+                        .token_accounts
+                        .iter()
+                        .map(|(addr, token_account)| TokenAccountOutput {
+                            address: addr.to_string(),
+                            token_account: token_account.clone(),
+                        })
+                        .collect(),
+                    usd_values: self.values.clone(),
+                };
+                writeln!(out, "{}", serde_json::to_string_pretty(&output)?)?;
+            }
+            OutputFormat::Csv => {
+                writeln!(out, "{}", CSV_HEADER)?;
+                for (addr, token_account) in &self.token_accounts {
+                    report::write_csv_row(out, addr, "token", Some(&token_account.mint), None, &token_account.amount)?;
+                }
+                for report in &self.reports {
+                    report.write_csv(out)?;
+                }
+            }
+            OutputFormat::Text => {
+                for (addr, token_account) in &self.token_accounts {
+                    write_token_account_summary(out, addr, token_account)?;
+                }
+                for (i, report) in self.reports.iter().enumerate() {
+                    write_wallet_summary(out, report)?;
+                    if let Some(values) = &self.values {
+                        write_wallet_value(out, &values[i])?;
+                    }
+                }
+                if self.reports.len() > 1 {
+                    write_combined_summary(out, &CombinedReport::new(self.reports.clone())?)?;
+                    if let Some(values) = &self.values {
+                        let total: f64 = values.iter().map(|value| value.total_usd).sum();
+                        writeln!(out, "- Total USD Value: ${:.2}", total)?;
+                    }
+                }
+            }
+            OutputFormat::Stats => write_stats_summary(out, &StatsReport::new(&self.reports)?)?,
+        }
+        Ok(())
+    }
+
+    /// Write a line per wallet balance that changed since `previous`, in the summary of the same addresses
+    ///
+    /// Wallets are matched by address, as the top wallets may change between summaries.
+    fn write_changes_since<W: Write>(&self, out: &mut W, previous: &Summary) -> Result<()> {
+        for report in &self.reports {
+            let Some(previous) = previous.reports.iter().find(|previous| previous.address == report.address) else {
+                continue;
+            };
+            for change in report.changes_since(previous)? {
+                writeln!(out, "{} {}: {} -> {} ({:+})", change.address, change.asset,
+                         change.before.ui_amount_string(), change.after.ui_amount_string(),
+                         change.after.ui_amount() - change.before.ui_amount())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// JSON output for a single wallet
+#[derive(Serialize)]
+struct WalletOutput<'a> {
+    #[serde(flatten)]
+    report: &'a WalletReport,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    usd_value: Option<&'a WalletValue>,
+}
+
+/// JSON output for more than one address
+#[derive(Serialize)]
+struct MultiAddressOutput {
+    #[serde(flatten)]
+    combined: CombinedReport,
+    token_accounts: Vec<TokenAccountOutput>,
+    /// The USD values of the wallets, in the order of `wallets`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    usd_values: Option<Vec<WalletValue>>,
+}
+
+#[derive(Serialize)]
+struct TokenAccountOutput {
+    address: String,
+    #[serde(flatten)]
+    token_account: TokenAccountInfo,
+}
+
+fn write_token_account_summary<W: Write>(out: &mut W, addr: &Pubkey, token_account: &TokenAccountInfo) -> Result<()> {
+    writeln!(out, "
+Token Account Summary for address: {}
+- Mint: {}
+- Owner: {}
+- Token Program: {}
+- Balance: {}
+    ", addr, token_account.mint, token_account.owner, token_account.token_program, token_account.amount.ui_amount())?;
+    Ok(())
+}
+
+fn write_wallet_summary<W: Write>(out: &mut W, report: &WalletReport) -> Result<()> {
+    let (fee_0, fee_1) = report.total_unclaimed_fees()?;
+    writeln!(out, "
 SOL Balance/Position Summary for address: {}
 - SOL: {}
 - WSOL: {}
 - SOL Unified (SOL + WSOL): {}
 - SOL in SOL-USDC.1bp LP Position: {}
-    ", addr, balance_sol, balance_wsol, balance_sol_unified, balance_sol_position);
+- Unclaimed Fees of SOL-USDC.1bp LP Position: {} SOL + {} USDC
+    ", report.address, report.sol.ui_amount(), report.wsol.ui_amount(), report.sol_unified.ui_amount(),
+             report.total_amount_0()?.ui_amount_string(), fee_0.ui_amount_string(), fee_1.ui_amount_string())?;
+    for reward in report.total_rewards()? {
+        writeln!(out, "- Pending Rewards of SOL-USDC.1bp LP Position: {} of {}", reward.amount.ui_amount(),
+                 reward.mint)?;
+    }
+    for range in &report.duplicate_ranges {
+        writeln!(out, "Warning: {} positions share the tick range [{}, {}), consolidated: {} SOL + {} USDC",
+                 range.positions.len(), range.tick_lower_index, range.tick_upper_index,
+                 range.amount_0.ui_amount(), range.amount_1.ui_amount())?;
+        for position in &range.positions {
+            writeln!(out, "  - Position {}", position)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_wallet_value<W: Write>(out: &mut W, value: &WalletValue) -> Result<()> {
+    match value.sol_unified_usd {
+        Some(sol_unified_usd) => writeln!(out, "- USD Value of SOL Unified: ${:.2}", sol_unified_usd)?,
+        None => writeln!(out, "- USD Value of SOL Unified: unknown")?,
+    }
+    for position in &value.positions {
+        writeln!(out, "- USD Value of LP Position {}: ${:.2}", position.position, position.usd)?;
+    }
+    writeln!(out, "- Total USD Value: ${:.2}", value.total_usd)?;
+    for mint in &value.unpriced_mints {
+        writeln!(out, "  (left out {}, which no pool prices)", mint)?;
+    }
+    Ok(())
+}
+
+fn write_combined_summary<W: Write>(out: &mut W, combined: &CombinedReport) -> Result<()> {
+    let total = &combined.total;
+    writeln!(out, "
+Combined SOL Balance/Position Summary for {} addresses:
+- SOL: {}
+- WSOL: {}
+- SOL Unified (SOL + WSOL): {}
+- SOL in SOL-USDC.1bp LP Positions: {} ({} positions)
+    ", combined.wallets.len(), total.sol.ui_amount(), total.wsol.ui_amount(), total.sol_unified.ui_amount(),
+             total.amount_0.ui_amount(), total.positions)?;
+    Ok(())
+}
+
+fn write_stats_summary<W: Write>(out: &mut W, stats: &StatsReport) -> Result<()> {
+    writeln!(out, "\nBalance Distribution of {} wallets:", stats.wallets)?;
+    write_amount_stats(out, "SOL Unified (SOL + WSOL)", &stats.sol_unified)?;
+    write_amount_stats(out, "SOL in SOL-USDC.1bp LP Positions", &stats.lp_token_0)?;
+    write_amount_stats(out, "USDC in SOL-USDC.1bp LP Positions", &stats.lp_token_1)?;
+    Ok(())
+}
+
+fn write_amount_stats<W: Write>(out: &mut W, name: &str, stats: &AmountStats) -> Result<()> {
+    writeln!(out, "- {}: min {}, median {}, p95 {}, max {}", name, stats.min.ui_amount_string(),
+             stats.median.ui_amount_string(), stats.p95.ui_amount_string(), stats.max.ui_amount_string())?;
+    for bucket in &stats.histogram {
+        writeln!(out, "  - [{}, {}): {} wallets", bucket.lower.ui_amount_string(), bucket.upper.ui_amount_string(),
+                 bucket.wallets)?;
+    }
+    Ok(())
+}
+
+fn pool_stats_command(balance_fetcher: &BalanceFetcher, pool_id: &PoolId) -> Result<()> {
+    let progress_bar = ProgressBar::new(0);
+    progress_bar.set_style(
+        ProgressStyle::with_template("[{elapsed_precise}] {bar:40} {pos}/{len} requests, {msg} (ETA {eta})")?,
+    );
+    let stats = balance_fetcher.raydium_pool_stats_with_progress(pool_id, |progress| {
+        progress_bar.set_length(progress.steps_total as u64);
+        progress_bar.set_position(progress.steps_done as u64);
+        progress_bar.set_message(format!("{} positions", progress.accounts_fetched));
+    })?;
+    progress_bar.finish_and_clear();
+    let name = pool_id.known_name().map(|name| format!(" ({})", name)).unwrap_or_default();
+    println!("
+Raydium Pool Stats for pool: {}{}
+- Positions: {} ({} with liquidity)
+- Total Liquidity: {}
+- In-range Liquidity: {} ({:.2}%)
+- Current Tick: {}
+- Fee Growth Global 0 (Q64.64): {}
+- Fee Growth Global 1 (Q64.64): {}
+    ", pool_id, name, stats.positions, stats.open_positions, stats.total_liquidity, stats.in_range_liquidity,
+             stats.in_range_fraction() * 100.0, stats.tick_current, stats.fee_growth_global_0_x64,
+             stats.fee_growth_global_1_x64);
+    Ok(())
+}
+
+fn pool_price_command(balance_fetcher: &BalanceFetcher, pool_id: &PoolId) -> Result<()> {
+    let price = balance_fetcher.pool_price(pool_id)?;
+    let name = pool_id.known_name().map(|name| format!(" ({})", name)).unwrap_or_default();
+    println!("
+Raydium Pool Price for pool: {}{}
+- Token 0 in Token 1: {} ({} per {})
+- Token 1 in Token 0: {} ({} per {})
+- Current Tick: {}
+- Sqrt Price (Q64.64): {}
+    ", pool_id, name, price.price_0_in_1, price.mint_1, price.mint_0, price.price_1_in_0, price.mint_0, price.mint_1,
+             price.tick_current, price.sqrt_price_x64);
+    Ok(())
+}
+
+fn exit_value_command(balance_fetcher: &BalanceFetcher, addr: &Pubkey) -> Result<()> {
+    let exit_values = balance_fetcher.position_exit_values_sol_usdc_1bp(addr)?;
+    // The pool is cached, this only reads the decimals of its mints
+    let price = balance_fetcher.pool_price(&PoolId::sol_usdc_1bp())?;
+    let sol = |amount| Amount { amount, decimals: price.decimals_0 };
+    let usdc = |amount| Amount { amount, decimals: price.decimals_1 };
+    println!("
+SOL-USDC.1bp LP Positions if withdrawn and swapped to USDC now, for address: {}", addr);
+    let mut total = usdc(0);
+    for exit_value in &exit_values {
+        println!("- Position {}: {} SOL + {} USDC => {} USDC (price impact {:.4}%)",
+                 exit_value.position,
+                 sol(exit_value.amount_0).ui_amount_string(),
+                 usdc(exit_value.amount_1).ui_amount_string(),
+                 usdc(exit_value.exit_amount).ui_amount_string(),
+                 exit_value.price_impact * 100.0);
+        total = total.checked_add(&usdc(exit_value.exit_amount))?;
+    }
+    println!("- Total: {} USDC\n", total.ui_amount_string());
     Ok(())
 }
+
+fn preflight_command(balance_fetcher: &BalanceFetcher, addr: &Pubkey, mut mints: Vec<MintAddress>) -> Result<()> {
+    // Check the tokens this tool tracks by default
+    if mints.is_empty() {
+        mints = vec![MintAddress::wsol(), MintAddress::usdc()];
+    }
+
+    let missing = balance_fetcher.missing_token_accounts(addr, &mints)?;
+    println!("
+Associated Token Account Preflight for address: {}
+- Checked mints: {}
+- Missing accounts: {}", addr, mints.len(), missing.len());
+    let sol = |lamports| Amount { amount: lamports, decimals: 9 };
+    let mut total_rent = sol(0);
+    for account in &missing {
+        println!("  - Mint {}: create {} (rent {} SOL)",
+                 account.mint, account.address, sol(account.rent).ui_amount_string());
+        total_rent = total_rent.checked_add(&sol(account.rent))?;
+    }
+    println!("- Total rent: {} SOL\n", total_rent.ui_amount_string());
+    Ok(())
+}
+
+fn tokens_command(balance_fetcher: &BalanceFetcher, addr: &Pubkey) -> Result<()> {
+    let balances = balance_fetcher.all_token_balances(addr)?;
+    let mints: Vec<Pubkey> = balances.iter().map(|balance| balance.mint).collect();
+    let metadata = balance_fetcher.token_metadata(&mints)?;
+    println!("
+Token Balances for address: {}
+- Mints: {}", addr, balances.len());
+    for (balance, metadata) in balances.iter().zip(metadata) {
+        let amount = Amount { amount: balance.amount, decimals: balance.decimals };
+        // Mints without metadata are listed by address only
+        let symbol = metadata.map(|metadata| format!(" ({})", metadata.symbol)).unwrap_or_default();
+        println!("  - Mint {}{}: {} in {} account(s)",
+                 balance.mint, symbol, amount.ui_amount_string(), balance.token_accounts.len());
+    }
+    println!();
+    Ok(())
+}
+
+fn positions_command(balance_fetcher: &BalanceFetcher, addr: &Pubkey) -> Result<()> {
+    let all_positions = balance_fetcher.all_raydium_positions(addr)?;
+    println!("
+Raydium CLMM Positions for address: {}
+- Pools: {}", addr, all_positions.len());
+    for pool in &all_positions {
+        println!("  - Pool {} ({} / {}): {} + {} in {} position(s)", pool.pool_id, pool.mint_0, pool.mint_1,
+                 pool.amount_0.ui_amount_string(), pool.amount_1.ui_amount_string(), pool.positions.len());
+        for position in &pool.positions {
+            let range = if position.in_range { "in range" } else { "out of range" };
+            println!("    - Position {} [{}, {}) {}: {} + {}", position.position, position.tick_lower_index,
+                     position.tick_upper_index, range, position.amount_0.ui_amount_string(),
+                     position.amount_1.ui_amount_string());
+        }
+    }
+    println!();
+    Ok(())
+}
+
+fn doctor_command(balance_fetcher: &BalanceFetcher) -> Result<()> {
+    let checks = balance_fetcher.check_rpc();
+    println!("
+RPC Checks for endpoint: {}", balance_fetcher.rpc.url());
+    for check in &checks {
+        match &check.result {
+            Ok(summary) => {
+                println!("  - {} ({}): ok in {:?}, {}", check.method, check.used_for, check.latency, summary);
+            }
+            Err(err) => println!("  - {} ({}): FAILED in {:?}, {}", check.method, check.used_for, check.latency, err),
+        }
+    }
+    println!();
+    let failed = checks.iter().filter(|check| check.result.is_err()).count();
+    if failed > 0 {
+        return Err(anyhow!("{} of {} RPC checks failed", failed, checks.len()));
+    }
+    Ok(())
+}
+
+/// Read addresses one per line, skipping blank lines and `#` comments
+fn read_addresses<R: BufRead>(reader: R) -> Result<Vec<Pubkey>> {
+    let mut addresses = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let addr = Pubkey::from_str(line).map_err(|_| anyhow!("invalid address {:?} at line {}", line, i + 1))?;
+        addresses.push(addr);
+    }
+    Ok(addresses)
+}
+
+/// Parse addresses separated by commas or whitespace
+fn parse_address_list(addresses: &str) -> Result<Vec<Pubkey>> {
+    addresses
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|addr| !addr.is_empty())
+        .map(|addr| Pubkey::from_str(addr).map_err(|_| anyhow!("invalid address {:?}", addr)))
+        .collect()
+}
+
+fn parse_address(addr: &str) -> std::result::Result<Pubkey, String> {
+    Pubkey::from_str(addr)
+        .map_err(|_| "Invalid address. Good address example: 53zSj4G935ZY2a5x2UnGAiJXSuXXmGHaLph2zhAUvYpg".to_string())
+}
+
+fn parse_mint_address(mint: &str) -> std::result::Result<MintAddress, String> {
+    MintAddress::from_str(mint)
+        .map_err(|err| format!("{}. Good mint address example: EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v", err))
+}
+
+fn parse_pyth_feed(feed: &str) -> std::result::Result<(MintAddress, Pubkey), String> {
+    let (mint, price_account) = feed.split_once('=').ok_or_else(|| {
+        "Expected MINT=PRICE_ACCOUNT, e.g. EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v=\
+         Dpw1EAVrSB1ibxiDQyTAW6Zip3J4Btk2x4SgApQCeFbX"
+            .to_string()
+    })?;
+    Ok((parse_mint_address(mint)?, parse_address(price_account)?))
+}
+
+fn parse_pool_id(pool_id: &str) -> std::result::Result<PoolId, String> {
+    PoolId::from_str(pool_id)
+        .map_err(|err| format!("{}. Good pool id example: 8sLbNZoA1cfnvMJLPfp98ZLAnFSYCFApfJKMbiXNLwxj", err))
+}
@@ -0,0 +1,491 @@
+use anchor_lang::AccountDeserialize;
+use raydium_amm_v3::libraries::{get_delta_amount_0_unsigned, get_delta_amount_1_unsigned, liquidity_math, tick_math};
+use raydium_amm_v3::libraries::{MulDiv, U256};
+use raydium_amm_v3::states::{PersonalPositionState, PoolState, RewardInfo, TickState};
+use solana_sdk::pubkey::Pubkey;
+use crate::error::{BalanceFetcherError, Result};
+
+pub const MIN_TICK: i32 = tick_math::MIN_TICK;
+pub const MAX_TICK: i32 = tick_math::MAX_TICK;
+
+/// Square root price at a tick, as a Q64.64 fixed point number
+pub fn sqrt_price_at_tick(tick: i32) -> Result<u128> {
+    if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+        return Err(BalanceFetcherError::Math(format!("tick {} out of range [{}, {}]", tick, MIN_TICK, MAX_TICK)));
+    }
+    Ok(tick_math::get_sqrt_price_at_tick(tick)?)
+}
+
+/// The greatest tick whose square root price is less than or equal to `sqrt_price_x64`
+pub fn tick_at_sqrt_price(sqrt_price_x64: u128) -> Result<i32> {
+    if !(tick_math::MIN_SQRT_PRICE_X64..tick_math::MAX_SQRT_PRICE_X64).contains(&sqrt_price_x64) {
+        return Err(BalanceFetcherError::Math(format!("sqrt price {} out of range", sqrt_price_x64)));
+    }
+    Ok(tick_math::get_tick_at_sqrt_price(sqrt_price_x64)?)
+}
+
+/// Token amounts of liquidity in a tick range, with the pool price at `current_tick`
+///
+/// Amounts are rounded down, i.e. what withdrawing the liquidity would return. Use
+/// `amounts_for_liquidity_at_sqrt_price` with the pool's `sqrt_price_x64` for the exact price within the tick.
+///
+/// # Arguments
+/// - `tick_lower`, `tick_upper` - The tick range of the liquidity
+/// - `current_tick` - The current tick of the pool
+/// - `liquidity` - The liquidity
+///
+/// # Returns
+/// - `(u64, u64)` - The amounts of token 0 and token 1: all token 0 below the range, all token 1 above it
+pub fn amounts_for_liquidity(
+    tick_lower: i32,
+    tick_upper: i32,
+    current_tick: i32,
+    liquidity: u128,
+) -> Result<(u64, u64)> {
+    let sqrt_price_x64 = sqrt_price_at_tick(current_tick.clamp(MIN_TICK, MAX_TICK))?;
+    amounts_for_liquidity_at_sqrt_price(tick_lower, tick_upper, sqrt_price_x64, liquidity)
+}
+
+/// Token amounts of liquidity in a tick range, with the pool price at `sqrt_price_x64`
+///
+/// # Arguments
+/// - `tick_lower`, `tick_upper` - The tick range of the liquidity
+/// - `sqrt_price_x64` - The square root price of the pool, as a Q64.64 fixed point number
+/// - `liquidity` - The liquidity
+///
+/// # Returns
+/// - `(u64, u64)` - The amounts of token 0 and token 1, rounded down
+pub fn amounts_for_liquidity_at_sqrt_price(
+    tick_lower: i32,
+    tick_upper: i32,
+    sqrt_price_x64: u128,
+    liquidity: u128,
+) -> Result<(u64, u64)> {
+    if tick_lower >= tick_upper {
+        return Err(BalanceFetcherError::InvalidInput(format!(
+            "tick lower {} must be less than tick upper {}",
+            tick_lower, tick_upper
+        )));
+    }
+    let sqrt_price_lower_x64 = sqrt_price_at_tick(tick_lower)?;
+    let sqrt_price_upper_x64 = sqrt_price_at_tick(tick_upper)?;
+    // Below the range the liquidity is all token 0, above the range it is all token 1
+    let sqrt_price_x64 = sqrt_price_x64.clamp(sqrt_price_lower_x64, sqrt_price_upper_x64);
+    let amount_0 = get_delta_amount_0_unsigned(sqrt_price_x64, sqrt_price_upper_x64, liquidity, false)?;
+    let amount_1 = get_delta_amount_1_unsigned(sqrt_price_lower_x64, sqrt_price_x64, liquidity, false)?;
+    Ok((amount_0, amount_1))
+}
+
+/// The maximum liquidity that available token amounts can provide in a tick range, the inverse of
+/// `amounts_for_liquidity_at_sqrt_price`
+///
+/// Useful for planning a new position size. Below the range only token 0 is used, above the range only token 1,
+/// and in range the token running out first limits the liquidity.
+///
+/// # Arguments
+/// - `tick_lower`, `tick_upper` - The tick range of the position
+/// - `sqrt_price_x64` - The square root price of the pool, as a Q64.64 fixed point number
+/// - `amount_0`, `amount_1` - The available amounts of token 0 and token 1
+///
+/// # Returns
+/// - `u128` - The liquidity
+pub fn liquidity_for_amounts(
+    tick_lower: i32,
+    tick_upper: i32,
+    sqrt_price_x64: u128,
+    amount_0: u64,
+    amount_1: u64,
+) -> Result<u128> {
+    if tick_lower >= tick_upper {
+        return Err(BalanceFetcherError::InvalidInput(format!(
+            "tick lower {} must be less than tick upper {}",
+            tick_lower, tick_upper
+        )));
+    }
+    let sqrt_price_lower_x64 = sqrt_price_at_tick(tick_lower)?;
+    let sqrt_price_upper_x64 = sqrt_price_at_tick(tick_upper)?;
+    Ok(liquidity_math::get_liquidity_from_amounts(
+        sqrt_price_x64,
+        sqrt_price_lower_x64,
+        sqrt_price_upper_x64,
+        amount_0,
+        amount_1,
+    ))
+}
+
+/// Token amounts of a position, computed from account data only
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PositionAmounts {
+    /// Amounts of token 0 and token 1 received when withdrawing all liquidity now
+    pub amount_0: u64,
+    pub amount_1: u64,
+    /// Fees owed as of the last time the position was updated on chain
+    pub fees_owed_0: u64,
+    pub fees_owed_1: u64,
+}
+
+/// Compute the token amounts of a position from already fetched account states, without any RPC call
+///
+/// # Arguments
+/// - `position` - The position state
+/// - `pool` - The state of the position's pool
+///
+/// # Returns
+/// - `PositionAmounts` - The withdrawable amounts and owed fees of the position
+pub fn position_amounts(position: &PersonalPositionState, pool: &PoolState) -> Result<PositionAmounts> {
+    let (amount_0, amount_1) = amounts_for_liquidity_at_sqrt_price(
+        position.tick_lower_index,
+        position.tick_upper_index,
+        pool.sqrt_price_x64,
+        position.liquidity,
+    )?;
+    Ok(PositionAmounts {
+        amount_0,
+        amount_1,
+        fees_owed_0: position.token_fees_owed_0,
+        fees_owed_1: position.token_fees_owed_1,
+    })
+}
+
+/// Same as `position_amounts`, but from raw account data, e.g. as stored by an indexer
+///
+/// # Arguments
+/// - `position_data` - The data of the position account, including the anchor discriminator
+/// - `pool_data` - The data of the pool account, including the anchor discriminator
+///
+/// # Returns
+/// - `PositionAmounts` - The withdrawable amounts and owed fees of the position
+pub fn position_amounts_from_account_data(position_data: &[u8], pool_data: &[u8]) -> Result<PositionAmounts> {
+    let position = PersonalPositionState::try_deserialize(&mut &position_data[..])
+        .map_err(|err| BalanceFetcherError::Deserialize(err.to_string()))?;
+    let pool = PoolState::try_deserialize(&mut &pool_data[..])
+        .map_err(|err| BalanceFetcherError::Deserialize(err.to_string()))?;
+    position_amounts(&position, &pool)
+}
+
+/// Compute the unclaimed trading fees of a position, like the Raydium program does when collecting them
+///
+/// Unlike `PositionAmounts::fees_owed_0` and `fees_owed_1`, they include the fees earned since the position was last
+/// updated on chain, from the fee growth inside its range.
+///
+/// # Arguments
+/// - `position` - The position state
+/// - `pool` - The state of the position's pool
+/// - `tick_lower` - The state of the position's lower tick
+/// - `tick_upper` - The state of the position's upper tick
+///
+/// # Returns
+/// - `(u64, u64)` - The unclaimed fees of token 0 and token 1
+pub fn unclaimed_fees(
+    position: &PersonalPositionState,
+    pool: &PoolState,
+    tick_lower: &TickState,
+    tick_upper: &TickState,
+) -> Result<(u64, u64)> {
+    // PoolState and TickState are packed, copy fields out before use
+    let tick_current = pool.tick_current;
+    let (lower_outside_0, lower_outside_1) = (tick_lower.fee_growth_outside_0_x64, tick_lower.fee_growth_outside_1_x64);
+    let (upper_outside_0, upper_outside_1) = (tick_upper.fee_growth_outside_0_x64, tick_upper.fee_growth_outside_1_x64);
+    let inside = |global: u128, lower_outside: u128, upper_outside: u128| {
+        growth_inside(tick_current, position.tick_lower_index, position.tick_upper_index, global, lower_outside,
+                      upper_outside)
+    };
+    let inside_0 = inside(pool.fee_growth_global_0_x64, lower_outside_0, upper_outside_0);
+    let inside_1 = inside(pool.fee_growth_global_1_x64, lower_outside_1, upper_outside_1);
+    Ok((
+        amount_owed(inside_0, position.fee_growth_inside_0_last_x64, position.liquidity, position.token_fees_owed_0)?,
+        amount_owed(inside_1, position.fee_growth_inside_1_last_x64, position.liquidity, position.token_fees_owed_1)?,
+    ))
+}
+
+/// Reward emissions earned by a position and not collected yet
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PendingReward {
+    pub mint: Pubkey,
+    /// The raw amount, without decimals
+    pub amount: u64,
+}
+
+/// Compute the pending rewards of a position, for each of the up to three reward tokens of its pool
+///
+/// The reward growth of the pool is only updated on chain when the pool is touched, so it is brought up to
+/// `timestamp` first, as the program does before collecting rewards.
+///
+/// # Arguments
+/// - `position` - The position state
+/// - `pool` - The state of the position's pool
+/// - `tick_lower` - The state of the position's lower tick
+/// - `tick_upper` - The state of the position's upper tick
+/// - `timestamp` - The current Unix timestamp in seconds
+///
+/// # Returns
+/// - `Vec<PendingReward>` - The mint and pending amount of each initialized reward of the pool, in the pool's order
+pub fn pending_rewards(
+    position: &PersonalPositionState,
+    pool: &PoolState,
+    tick_lower: &TickState,
+    tick_upper: &TickState,
+    timestamp: u64,
+) -> Result<Vec<PendingReward>> {
+    // PoolState and TickState are packed, copy fields out before use
+    let (tick_current, liquidity, reward_infos) = (pool.tick_current, pool.liquidity, pool.reward_infos);
+    let lower_outside = tick_lower.reward_growths_outside_x64;
+    let upper_outside = tick_upper.reward_growths_outside_x64;
+    let mut rewards = Vec::new();
+    for (i, reward_info) in reward_infos.iter().enumerate() {
+        let mint = reward_info.token_mint;
+        if mint == Pubkey::default() {
+            continue;
+        }
+        let global = reward_growth_global_at(reward_info, liquidity, timestamp)?;
+        let inside = growth_inside(tick_current, position.tick_lower_index, position.tick_upper_index, global,
+                                   lower_outside[i], upper_outside[i]);
+        let position_reward = &position.reward_infos[i];
+        let (growth_inside_last, owed) = (position_reward.growth_inside_last_x64, position_reward.reward_amount_owed);
+        let amount = amount_owed(inside, growth_inside_last, position.liquidity, owed)?;
+        rewards.push(PendingReward { mint, amount });
+    }
+    Ok(rewards)
+}
+
+/// The reward growth per unit of liquidity of a pool at `timestamp`, in Q64.64
+fn reward_growth_global_at(reward_info: &RewardInfo, liquidity: u128, timestamp: u64) -> Result<u128> {
+    // RewardInfo is packed, copy fields out before use
+    let (open_time, end_time, last_update_time) =
+        (reward_info.open_time, reward_info.end_time, reward_info.last_update_time);
+    let (growth_global, emissions_per_second_x64) =
+        (reward_info.reward_growth_global_x64, reward_info.emissions_per_second_x64);
+    // Emissions stop at the end time, and nothing is emitted while the pool has no liquidity in range
+    let emitted_until = timestamp.min(end_time);
+    if timestamp <= open_time || liquidity == 0 || emitted_until <= last_update_time {
+        return Ok(growth_global);
+    }
+    let growth_delta = U256::from(emitted_until - last_update_time)
+        .mul_div_floor(U256::from(emissions_per_second_x64), U256::from(liquidity))
+        .filter(|growth_delta| *growth_delta <= U256::from(u128::MAX))
+        .ok_or_else(|| BalanceFetcherError::Math("reward growth overflows u128".to_string()))?;
+    Ok(growth_global.wrapping_add(growth_delta.as_u128()))
+}
+
+/// Growth per unit of liquidity inside a tick range, of fees or rewards, in Q64.64
+///
+/// Growth is tracked modulo 2^128 on chain, so differences wrap around.
+fn growth_inside(
+    tick_current: i32,
+    tick_lower_index: i32,
+    tick_upper_index: i32,
+    growth_global: u128,
+    lower_outside: u128,
+    upper_outside: u128,
+) -> u128 {
+    let below =
+        if tick_current >= tick_lower_index { lower_outside } else { growth_global.wrapping_sub(lower_outside) };
+    let above =
+        if tick_current < tick_upper_index { upper_outside } else { growth_global.wrapping_sub(upper_outside) };
+    growth_global.wrapping_sub(below).wrapping_sub(above)
+}
+
+/// Amount owed as of the last update plus the amount earned since, by the growth inside the range since then
+fn amount_owed(growth_inside: u128, growth_inside_last: u128, liquidity: u128, owed: u64) -> Result<u64> {
+    mul_shr_64(growth_inside.wrapping_sub(growth_inside_last), liquidity)
+        .and_then(|earned| u64::try_from(earned).ok())
+        .and_then(|earned| owed.checked_add(earned))
+        .ok_or_else(|| BalanceFetcherError::Math("amount owed overflows u64".to_string()))
+}
+
+/// `a * b / 2^64` rounded down, `None` if it overflows u128
+fn mul_shr_64(a: u128, b: u128) -> Option<u128> {
+    let (a_hi, a_lo) = (a >> 64, a & u64::MAX as u128);
+    let (b_hi, b_lo) = (b >> 64, b & u64::MAX as u128);
+    // The low halves multiply without overflow, and only their product has bits shifted out
+    a_hi.checked_mul(b)?.checked_add(a_lo * b_hi)?.checked_add((a_lo * b_lo) >> 64)
+}
+
+/// Human price of token 0 in token 1 from a Q64.64 square root price, adjusted by the tokens' decimals
+pub fn sqrt_price_x64_to_price(sqrt_price_x64: u128, decimals_0: u8, decimals_1: u8) -> f64 {
+    let sqrt_price = sqrt_price_x64 as f64 / (1u128 << 64) as f64;
+    sqrt_price * sqrt_price * 10f64.powi(decimals_0 as i32 - decimals_1 as i32)
+}
+
+/// Human price of token 0 in token 1 at a tick, e.g. for position tick bounds
+///
+/// # Arguments
+/// - `decimals_0`, `decimals_1` - The decimals of token 0 and token 1 of the pool
+/// - `tick` - The tick
+///
+/// # Returns
+/// - `f64` - How many token 1 one token 0 is worth at the tick
+pub fn tick_to_price(decimals_0: u8, decimals_1: u8, tick: i32) -> Result<f64> {
+    Ok(sqrt_price_x64_to_price(sqrt_price_at_tick(tick)?, decimals_0, decimals_1))
+}
+
+/// The usable tick nearest to a human price, e.g. to pick position tick bounds
+///
+/// # Arguments
+/// - `decimals_0`, `decimals_1` - The decimals of token 0 and token 1 of the pool
+/// - `price` - How many token 1 one token 0 is worth
+/// - `tick_spacing` - The tick spacing of the pool, the returned tick is a multiple of it
+///
+/// # Returns
+/// - `i32` - The nearest multiple of `tick_spacing` within the valid tick range
+pub fn price_to_nearest_tick(decimals_0: u8, decimals_1: u8, price: f64, tick_spacing: u16) -> Result<i32> {
+    if !(price.is_finite() && price > 0.0) {
+        return Err(BalanceFetcherError::InvalidInput(format!("price {} must be positive", price)));
+    }
+    if tick_spacing == 0 {
+        return Err(BalanceFetcherError::InvalidInput("tick spacing must be positive".to_string()));
+    }
+    let raw_price = price / 10f64.powi(decimals_0 as i32 - decimals_1 as i32);
+    let tick = raw_price.ln() / 1.0001f64.ln();
+    let tick_spacing = tick_spacing as i32;
+    let tick = (tick / tick_spacing as f64).round() as i32 * tick_spacing;
+    // Stay within the range of usable ticks, which are multiples of the tick spacing as well
+    Ok(tick.clamp(MIN_TICK / tick_spacing * tick_spacing, MAX_TICK / tick_spacing * tick_spacing))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_amounts_for_liquidity() {
+        let liquidity = 1_000_000_000;
+        let (amount_0, amount_1) = amounts_for_liquidity(-100, 100, -200, liquidity).unwrap();
+        assert!(amount_0 > 0);
+        assert_eq!(amount_1, 0);
+        let (amount_0, amount_1) = amounts_for_liquidity(-100, 100, 200, liquidity).unwrap();
+        assert_eq!(amount_0, 0);
+        assert!(amount_1 > 0);
+        let (amount_0, amount_1) = amounts_for_liquidity(-100, 100, 0, liquidity).unwrap();
+        assert!(amount_0 > 0);
+        assert!(amount_1 > 0);
+        assert!(amounts_for_liquidity(100, -100, 0, liquidity).is_err());
+        assert!(amounts_for_liquidity(-100, MAX_TICK + 1, 0, liquidity).is_err());
+    }
+
+    #[test]
+    fn test_liquidity_for_amounts() {
+        let sqrt_price_x64 = sqrt_price_at_tick(0).unwrap();
+        let (amount_0, amount_1) = amounts_for_liquidity(-100, 100, 0, 1_000_000_000).unwrap();
+        let liquidity = liquidity_for_amounts(-100, 100, sqrt_price_x64, amount_0, amount_1).unwrap();
+        // Amounts are rounded down, so the liquidity recovered from them can only be slightly lower
+        assert!(liquidity <= 1_000_000_000 && liquidity > 999_000_000);
+        // Only token 1 is left, so swapping it in would be needed for liquidity in range
+        assert_eq!(liquidity_for_amounts(-100, 100, sqrt_price_x64, 0, amount_1).unwrap(), 0);
+        assert!(liquidity_for_amounts(100, -100, sqrt_price_x64, amount_0, amount_1).is_err());
+    }
+
+    #[test]
+    fn test_position_amounts() {
+        let pool = PoolState { sqrt_price_x64: sqrt_price_at_tick(0).unwrap(), ..PoolState::default() };
+        let position = PersonalPositionState {
+            tick_lower_index: -100,
+            tick_upper_index: 100,
+            liquidity: 1_000_000_000,
+            token_fees_owed_0: 7,
+            ..PersonalPositionState::default()
+        };
+        let amounts = position_amounts(&position, &pool).unwrap();
+        assert_eq!((amounts.amount_0, amounts.amount_1), amounts_for_liquidity(-100, 100, 0, 1_000_000_000).unwrap());
+        assert_eq!((amounts.fees_owed_0, amounts.fees_owed_1), (7, 0));
+        assert!(position_amounts_from_account_data(&[0; 8], &[0; 8]).is_err());
+    }
+
+    #[test]
+    fn test_unclaimed_fees() {
+        let q64 = 1u128 << 64;
+        let pool = PoolState {
+            tick_current: 0,
+            fee_growth_global_0_x64: 10 * q64,
+            fee_growth_global_1_x64: 4 * q64,
+            ..PoolState::default()
+        };
+        let tick_lower = TickState { fee_growth_outside_0_x64: 2 * q64, ..TickState::default() };
+        let tick_upper = TickState { fee_growth_outside_0_x64: 3 * q64, ..TickState::default() };
+        let position = PersonalPositionState {
+            tick_lower_index: -100,
+            tick_upper_index: 100,
+            liquidity: 1_000,
+            fee_growth_inside_0_last_x64: q64,
+            // The inside growth of token 1 wrapped around since the last update
+            fee_growth_inside_1_last_x64: u128::MAX - q64 + 1,
+            token_fees_owed_0: 7,
+            ..PersonalPositionState::default()
+        };
+        // Inside growth is 10 - 2 - 3 = 5 for token 0 and 4 for token 1
+        assert_eq!(unclaimed_fees(&position, &pool, &tick_lower, &tick_upper).unwrap(), (4 * 1_000 + 7, 5 * 1_000));
+        // Out of range below, nothing is earned inside
+        let pool_below = PoolState { tick_current: -200, ..pool };
+        let tick_lower = TickState { fee_growth_outside_0_x64: 8 * q64, ..TickState::default() };
+        let tick_upper = TickState { fee_growth_outside_0_x64: 8 * q64, ..TickState::default() };
+        let position = PersonalPositionState { fee_growth_inside_0_last_x64: 0, ..position };
+        assert_eq!(unclaimed_fees(&position, &pool_below, &tick_lower, &tick_upper).unwrap().0, 7);
+    }
+
+    #[test]
+    fn test_pending_rewards() {
+        let q64 = 1u128 << 64;
+        let mut pool = PoolState { liquidity: 1, ..PoolState::default() };
+        let mint = Pubkey::new_unique();
+        pool.reward_infos[0] = RewardInfo {
+            token_mint: mint,
+            open_time: 0,
+            end_time: 1_000,
+            last_update_time: 100,
+            emissions_per_second_x64: q64,
+            reward_growth_global_x64: 4 * q64,
+            ..RewardInfo::default()
+        };
+        let mut position = PersonalPositionState {
+            tick_lower_index: -100,
+            tick_upper_index: 100,
+            liquidity: 1,
+            ..PersonalPositionState::default()
+        };
+        position.reward_infos[0].reward_amount_owed = 3;
+        let ticks = TickState::default();
+        let rewards = |timestamp| pending_rewards(&position, &pool, &ticks, &ticks, timestamp).unwrap();
+        // Uninitialized rewards are left out
+        assert_eq!(rewards(100), vec![PendingReward { mint, amount: 7 }]);
+        // One token per second is emitted since the last update, until the end time
+        assert_eq!(rewards(110)[0].amount, 17);
+        assert_eq!(rewards(5_000)[0].amount, 907);
+    }
+
+    #[test]
+    fn test_mul_shr_64() {
+        let q64 = 1u128 << 64;
+        assert_eq!(mul_shr_64(3 * q64, 5), Some(15));
+        assert_eq!(mul_shr_64(q64 + q64 / 2, 3), Some(4));
+        assert_eq!(mul_shr_64(u128::MAX, q64), Some(u128::MAX));
+        assert_eq!(mul_shr_64(u128::MAX, 2 * q64), None);
+    }
+
+    #[test]
+    fn test_tick_to_price() {
+        assert!((tick_to_price(0, 0, 0).unwrap() - 1.0).abs() < 1e-12);
+        // SOL (9 decimals) in USDC (6 decimals)
+        let price = tick_to_price(9, 6, -18420).unwrap();
+        assert!((price - 158.5).abs() < 0.1);
+        assert!(tick_to_price(0, 0, MAX_TICK + 1).is_err());
+    }
+
+    #[test]
+    fn test_price_to_nearest_tick() {
+        assert_eq!(price_to_nearest_tick(0, 0, 1.0, 1).unwrap(), 0);
+        let price = tick_to_price(9, 6, -18421).unwrap();
+        assert_eq!(price_to_nearest_tick(9, 6, price, 1).unwrap(), -18421);
+        assert_eq!(price_to_nearest_tick(9, 6, 158.5, 60).unwrap(), -18420);
+        assert_eq!(price_to_nearest_tick(9, 6, 158.5, 64).unwrap(), -18432);
+        assert_eq!(price_to_nearest_tick(0, 0, f64::MAX, 10).unwrap(), MAX_TICK / 10 * 10);
+        assert!(price_to_nearest_tick(0, 0, 0.0, 1).is_err());
+    }
+
+    #[test]
+    fn test_tick_at_sqrt_price() {
+        let sqrt_price_x64 = sqrt_price_at_tick(1234).unwrap();
+        assert_eq!(tick_at_sqrt_price(sqrt_price_x64).unwrap(), 1234);
+        assert_eq!(tick_at_sqrt_price(sqrt_price_x64 - 1).unwrap(), 1233);
+        assert!(tick_at_sqrt_price(0).is_err());
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+use serde::Serialize;
+use solana_sdk::account::Account;
+use solana_sdk::pubkey::Pubkey;
+use crate::balance_fetcher::{unix_timestamp, BalanceFetcher};
+use crate::error::{BalanceFetcherError, Result};
+use crate::ids::MintAddress;
+
+/// Program ID of the Pyth Solana receiver, owning the price update accounts of Pyth price feeds
+pub const PYTH_RECEIVER_PROGRAM_ID: &str = "rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ";
+
+/// Price feed account of SOL/USD, kept up to date by Pyth on mainnet
+pub const PYTH_SOL_USD_PRICE_ACCOUNT: &str = "7UVimffxr9ow1uXYxsr4LHAcV58mLzhmwaeKvJ1pjLiE";
+
+/// Price feed account of USDC/USD, kept up to date by Pyth on mainnet
+pub const PYTH_USDC_USD_PRICE_ACCOUNT: &str = "Dpw1EAVrSB1ibxiDQyTAW6Zip3J4Btk2x4SgApQCeFbX";
+
+/// Anchor discriminator of `PriceUpdateV2` accounts, the first 8 bytes of sha256("account:PriceUpdateV2")
+const PRICE_UPDATE_V2_DISCRIMINATOR: [u8; 8] = [34, 241, 35, 99, 157, 126, 244, 205];
+
+// PriceUpdateV2 layout: discriminator (8) + write_authority (32) + verification_level (1 or 2) + price message
+const VERIFICATION_LEVEL_OFFSET: usize = 8 + 32;
+
+/// A USD price of a whole token
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct TokenPrice {
+    pub price: f64,
+    /// Half the width of the price's confidence interval, if the source has one, e.g. 0.05 for $150 ± $0.05
+    pub confidence: Option<f64>,
+    /// The Unix timestamp the price was published at, if the source tells
+    pub publish_time: Option<i64>,
+}
+
+/// A source of USD prices of tokens
+pub trait PriceSource {
+    /// The USD price of a whole token of each of `mints`, in their order, `None` for mints the source does not price
+    /// or cannot price at the moment
+    fn usd_prices(&self, mints: &[MintAddress]) -> Result<Vec<Option<TokenPrice>>>;
+
+    /// Same as `usd_prices` for a single mint
+    fn usd_price(&self, mint: &MintAddress) -> Result<Option<TokenPrice>> {
+        Ok(self.usd_prices(std::slice::from_ref(mint))?.remove(0))
+    }
+}
+
+/// A price read from a Pyth price update account
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PythPrice {
+    /// The price in units of 10^`exponent`
+    pub price: i64,
+    /// The confidence, in units of 10^`exponent`
+    pub confidence: u64,
+    pub exponent: i32,
+    pub publish_time: i64,
+}
+
+impl PythPrice {
+    /// Read the price message from the data of a `PriceUpdateV2` account of the Pyth receiver
+    pub fn from_account_data(data: &[u8]) -> Result<Self> {
+        let too_short = || BalanceFetcherError::Deserialize(format!("price update too short: {} bytes", data.len()));
+        if data.get(..8) != Some(&PRICE_UPDATE_V2_DISCRIMINATOR[..]) {
+            return Err(BalanceFetcherError::Deserialize("not a Pyth price update account".to_string()));
+        }
+        // The verification level is a Borsh enum, `Partial { num_signatures: u8 }` or `Full`
+        let message_offset = match data.get(VERIFICATION_LEVEL_OFFSET).ok_or_else(too_short)? {
+            0 => VERIFICATION_LEVEL_OFFSET + 2,
+            1 => VERIFICATION_LEVEL_OFFSET + 1,
+            level => {
+                return Err(BalanceFetcherError::Deserialize(format!("unknown verification level {}", level)));
+            }
+        };
+        // Price message: feed_id (32) + price (8) + conf (8) + exponent (4) + publish_time (8) + ...
+        let message = data.get(message_offset..message_offset + 60).ok_or_else(too_short)?;
+        Ok(Self {
+            price: i64::from_le_bytes(message[32..40].try_into().unwrap()),
+            confidence: u64::from_le_bytes(message[40..48].try_into().unwrap()),
+            exponent: i32::from_le_bytes(message[48..52].try_into().unwrap()),
+            publish_time: i64::from_le_bytes(message[52..60].try_into().unwrap()),
+        })
+    }
+
+    /// The price and confidence in USD, failing with `BalanceFetcherError::StalePrice` if published more than
+    /// `max_age` before `now`, a Unix timestamp
+    pub fn to_token_price(&self, now: u64, max_age: Duration) -> Result<TokenPrice> {
+        let age = (now as i64).saturating_sub(self.publish_time);
+        if age > max_age.as_secs() as i64 {
+            return Err(BalanceFetcherError::StalePrice(format!(
+                "published {}s ago, more than the maximum age of {}s",
+                age,
+                max_age.as_secs()
+            )));
+        }
+        let scale = 10f64.powi(self.exponent);
+        Ok(TokenPrice {
+            price: self.price as f64 * scale,
+            confidence: Some(self.confidence as f64 * scale),
+            publish_time: Some(self.publish_time),
+        })
+    }
+}
+
+/// Prices of mints read from Pyth price feed accounts
+///
+/// Unlike prices of pools, oracle prices aggregate many publishers and cannot be moved by a single swap. SOL (as
+/// WSOL) and USDC are priced by default, more mints by `with_price_account`. A mint whose price feed account is
+/// missing, unreadable or stale is left unpriced with a warning rather than failing the prices of the others.
+pub struct PythPriceSource<'a> {
+    balance_fetcher: &'a BalanceFetcher,
+    price_accounts: HashMap<MintAddress, Pubkey>,
+    max_age: Duration,
+}
+
+impl<'a> PythPriceSource<'a> {
+    /// Price SOL and USDC by their mainnet price feeds, leaving out prices older than a minute
+    pub fn new(balance_fetcher: &'a BalanceFetcher) -> Self {
+        let price_accounts = HashMap::from([
+            (MintAddress::wsol(), Pubkey::from_str(PYTH_SOL_USD_PRICE_ACCOUNT).unwrap()),
+            (MintAddress::usdc(), Pubkey::from_str(PYTH_USDC_USD_PRICE_ACCOUNT).unwrap()),
+        ]);
+        Self { balance_fetcher, price_accounts, max_age: Duration::from_secs(60) }
+    }
+
+    /// Price `mint` by the Pyth price update account of its USD price feed, replacing the default one if any
+    pub fn with_price_account(mut self, mint: MintAddress, price_account: Pubkey) -> Self {
+        self.price_accounts.insert(mint, price_account);
+        self
+    }
+
+    /// Leave out prices published longer than `max_age` ago rather than value holdings at them
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+}
+
+impl PythPriceSource<'_> {
+    /// The price in the data of `price_account`, failing if it is not a fresh Pyth price update
+    fn token_price(&self, price_account: &Pubkey, account: Option<Account>, now: u64) -> Result<TokenPrice> {
+        let account = account.ok_or(BalanceFetcherError::MissingAccount(*price_account))?;
+        if account.owner != Pubkey::from_str(PYTH_RECEIVER_PROGRAM_ID).unwrap() {
+            return Err(BalanceFetcherError::Deserialize(format!(
+                "{} is not owned by the Pyth receiver program",
+                price_account
+            )));
+        }
+        PythPrice::from_account_data(&account.data)?.to_token_price(now, self.max_age)
+    }
+}
+
+impl PriceSource for PythPriceSource<'_> {
+    fn usd_prices(&self, mints: &[MintAddress]) -> Result<Vec<Option<TokenPrice>>> {
+        let price_accounts: Vec<Pubkey> =
+            mints.iter().filter_map(|mint| self.price_accounts.get(mint)).copied().collect();
+        let (_, accounts) = self.balance_fetcher.get_multiple_accounts_chunked(&price_accounts)?;
+        let now = unix_timestamp();
+        let mut accounts = price_accounts.iter().zip(accounts);
+        Ok(mints
+            .iter()
+            .map(|mint| {
+                if !self.price_accounts.contains_key(mint) {
+                    return None;
+                }
+                let (price_account, account) = accounts.next().unwrap();
+                match self.token_price(price_account, account, now) {
+                    Ok(price) => Some(price),
+                    Err(err) => {
+                        log::warn!("mint {} is not priced by Pyth price feed {}: {}", mint, price_account, err);
+                        None
+                    }
+                }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price_update_data(verification_level: &[u8], price: i64, confidence: u64, publish_time: i64) -> Vec<u8> {
+        [
+            PRICE_UPDATE_V2_DISCRIMINATOR.to_vec(),
+            vec![7; 32],
+            verification_level.to_vec(),
+            vec![9; 32],
+            price.to_le_bytes().to_vec(),
+            confidence.to_le_bytes().to_vec(),
+            (-8i32).to_le_bytes().to_vec(),
+            publish_time.to_le_bytes().to_vec(),
+            vec![0; 40],
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn test_pyth_price_from_account_data() {
+        let full = price_update_data(&[1], 15_012_345_678, 5_000_000, 1_700_000_000);
+        let price = PythPrice::from_account_data(&full).unwrap();
+        assert_eq!(
+            price,
+            PythPrice { price: 15_012_345_678, confidence: 5_000_000, exponent: -8, publish_time: 1_700_000_000 }
+        );
+        let partial = price_update_data(&[0, 5], 15_012_345_678, 5_000_000, 1_700_000_000);
+        assert_eq!(PythPrice::from_account_data(&partial).unwrap(), price);
+        assert!(PythPrice::from_account_data(&full[..80]).is_err());
+        assert!(PythPrice::from_account_data(&price_update_data(&[2], 1, 1, 1)).is_err());
+        assert!(PythPrice::from_account_data(&[0; 200]).is_err());
+    }
+
+    #[test]
+    fn test_pyth_price_to_token_price() {
+        let price =
+            PythPrice { price: 15_012_345_678, confidence: 5_000_000, exponent: -8, publish_time: 1_700_000_000 };
+        let token_price = price.to_token_price(1_700_000_030, Duration::from_secs(60)).unwrap();
+        assert!((token_price.price - 150.12345678).abs() < 1e-9);
+        assert!((token_price.confidence.unwrap() - 0.05).abs() < 1e-12);
+        assert_eq!(token_price.publish_time, Some(1_700_000_000));
+        let stale = price.to_token_price(1_700_000_061, Duration::from_secs(60));
+        assert!(matches!(stale, Err(BalanceFetcherError::StalePrice(_))));
+    }
+
+    #[test]
+    fn test_pyth_price_source_token_price() {
+        let balance_fetcher = BalanceFetcher::new("https://api.mainnet-beta.solana.com");
+        let price_source = PythPriceSource::new(&balance_fetcher);
+        let price_account = Pubkey::new_unique();
+        let account = Account {
+            owner: Pubkey::from_str(PYTH_RECEIVER_PROGRAM_ID).unwrap(),
+            data: price_update_data(&[1], 15_012_345_678, 5_000_000, 1_700_000_000),
+            ..Account::default()
+        };
+        assert!(price_source.token_price(&price_account, Some(account.clone()), 1_700_000_030).is_ok());
+        let stale = price_source.token_price(&price_account, Some(account.clone()), 1_700_000_061);
+        assert!(matches!(stale, Err(BalanceFetcherError::StalePrice(_))));
+        let missing = price_source.token_price(&price_account, None, 1_700_000_030);
+        assert!(matches!(missing, Err(BalanceFetcherError::MissingAccount(_))));
+        let not_pyth = Account { owner: Pubkey::new_unique(), ..account };
+        let not_pyth = price_source.token_price(&price_account, Some(not_pyth), 1_700_000_030);
+        assert!(matches!(not_pyth, Err(BalanceFetcherError::Deserialize(_))));
+    }
+}
@@ -0,0 +1,83 @@
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use anyhow::anyhow;
+use solana_sdk::pubkey::Pubkey;
+
+type Result<T> = anyhow::Result<T>;
+
+/// How many recently entered addresses are remembered and offered
+const HISTORY_LEN: usize = 10;
+
+/// The file recently entered addresses are remembered in, `RBF_HISTORY_FILE` or else in the home directory
+fn history_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("RBF_HISTORY_FILE") {
+        return Some(PathBuf::from(path));
+    }
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".raydium-balance-fetcher-history"))
+}
+
+/// Read the recently entered addresses, most recent first, skipping lines that are not addresses
+fn read_history() -> Vec<Pubkey> {
+    let Some(content) = history_path().and_then(|path| fs::read_to_string(path).ok()) else {
+        return vec![];
+    };
+    content.lines().filter_map(|line| Pubkey::from_str(line.trim()).ok()).take(HISTORY_LEN).collect()
+}
+
+/// Move `address` to the front of the history, which is best effort as the balances are what the user is after
+fn remember(history: &[Pubkey], address: Pubkey) {
+    let Some(path) = history_path() else {
+        return;
+    };
+    let addresses = std::iter::once(address).chain(history.iter().copied().filter(|addr| *addr != address));
+    let content: String = addresses.take(HISTORY_LEN).map(|addr| format!("{}\n", addr)).collect();
+    if let Err(err) = fs::write(&path, content) {
+        log::warn!("failed to save address history to {}: {}", path.display(), err);
+    }
+}
+
+/// Ask for a wallet address on the terminal until a valid one is entered, offering recently entered addresses
+///
+/// An empty line picks the most recent address, a number one of the listed ones.
+pub(crate) fn prompt_address() -> Result<Pubkey> {
+    let history = read_history();
+    let stdin = io::stdin();
+    let mut stderr = io::stderr();
+    if !history.is_empty() {
+        writeln!(stderr, "Recent addresses:")?;
+        for (i, address) in history.iter().enumerate() {
+            writeln!(stderr, "  {}. {}", i + 1, address)?;
+        }
+    }
+    loop {
+        if history.is_empty() {
+            write!(stderr, "Wallet address: ")?;
+        } else {
+            write!(stderr, "Wallet address, or number of a recent one [1]: ")?;
+        }
+        stderr.flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Err(anyhow!("no address entered"));
+        }
+        let line = line.trim();
+        let address = match line.parse::<usize>() {
+            _ if line.is_empty() => history.first().copied(),
+            Ok(n) if (1..=history.len()).contains(&n) => Some(history[n - 1]),
+            _ => Pubkey::from_str(line).ok(),
+        };
+        match address {
+            Some(address) => {
+                remember(&history, address);
+                return Ok(address);
+            }
+            None => writeln!(
+                stderr,
+                "Invalid address. Good address example: 53zSj4G935ZY2a5x2UnGAiJXSuXXmGHaLph2zhAUvYpg"
+            )?,
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Token bucket refilled at a fixed rate, allowing bursts up to one second worth of requests
+struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    fn new(requests_per_second: f64, now: Instant) -> Self {
+        let capacity = requests_per_second.max(1.0);
+        Self { rate: requests_per_second, capacity, tokens: capacity, refilled_at: now }
+    }
+
+    /// Take a token at `now`, or return how long to wait until one is available
+    fn take(&mut self, now: Instant) -> Option<Duration> {
+        let elapsed = now.saturating_duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.refilled_at = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            None
+        } else {
+            Some(Duration::from_secs_f64((1.0 - self.tokens) / self.rate))
+        }
+    }
+}
+
+/// Limit of the request rate shared by all requests of an RPC client
+pub(crate) struct RateLimiter {
+    bucket: Mutex<TokenBucket>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(requests_per_second: f64) -> Self {
+        Self { bucket: Mutex::new(TokenBucket::new(requests_per_second, Instant::now())) }
+    }
+
+    /// Wait until a request may be sent
+    pub(crate) async fn acquire(&self) {
+        loop {
+            // Released before sleeping, so that other requests can take tokens refilled in the meantime
+            let wait = self.bucket.lock().unwrap().take(Instant::now());
+            match wait {
+                None => return,
+                Some(wait) => tokio::time::sleep(wait).await,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(2.0, start);
+        // A burst up to the capacity, then one request every 500ms
+        assert_eq!(bucket.take(start), None);
+        assert_eq!(bucket.take(start), None);
+        assert_eq!(bucket.take(start), Some(Duration::from_millis(500)));
+        assert_eq!(bucket.take(start + Duration::from_millis(500)), None);
+        assert!(bucket.take(start + Duration::from_millis(600)).is_some());
+        // Idle time does not accumulate more than the capacity
+        let later = start + Duration::from_secs(60);
+        assert_eq!(bucket.take(later), None);
+        assert_eq!(bucket.take(later), None);
+        assert!(bucket.take(later).is_some());
+    }
+
+    #[test]
+    fn test_token_bucket_below_one_per_second() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(0.5, start);
+        assert_eq!(bucket.take(start), None);
+        assert_eq!(bucket.take(start), Some(Duration::from_secs(2)));
+    }
+}
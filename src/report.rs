@@ -0,0 +1,932 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::str::FromStr;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use raydium_amm_v3::states::{PersonalPositionState, PoolState, TickState};
+use solana_sdk::pubkey::Pubkey;
+use spl_associated_token_account::get_associated_token_address;
+use spl_token_2022::extension::StateWithExtensions;
+use crate::balance_fetcher::{
+    deserialize_anchor_account, percentile, pool_positions_from_accounts, unix_timestamp, BalanceFetcher, SOL_DECIMALS,
+    WSOL_MINT_ADDRESS,
+};
+use crate::cache::AccountKind;
+use crate::error::{BalanceFetcherError, Result};
+use crate::ids::{MintAddress, PoolId};
+use crate::math;
+
+/// Header of the CSV rows written by `WalletReport::write_csv` and `write_csv_row`
+pub const CSV_HEADER: &str = "address,asset,mint,position,raw_amount,decimals,ui_amount";
+
+/// Machine-readable balances and LP positions of a wallet in a Raydium pool
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct WalletReport {
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub address: Pubkey,
+    /// The slot the SOL balance was read at, other accounts are read at this slot or later
+    pub slot: u64,
+    pub sol: Amount,
+    pub wsol: Amount,
+    /// SOL and WSOL
+    pub sol_unified: Amount,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub pool_id: Pubkey,
+    pub positions: Vec<PositionReport>,
+    /// Positions sharing the same tick range, which is usually a mistake such as a scripted reopen run twice
+    pub duplicate_ranges: Vec<DuplicateRange>,
+}
+
+/// An LP position of `WalletReport`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct PositionReport {
+    /// The position PDA
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub position: Pubkey,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub nft_mint: Pubkey,
+    pub tick_lower_index: i32,
+    pub tick_upper_index: i32,
+    /// Whether the current price of the pool is within the tick range, so the position earns fees
+    pub in_range: bool,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub mint_0: Pubkey,
+    pub amount_0: Amount,
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub mint_1: Pubkey,
+    pub amount_1: Amount,
+    /// Trading fees of token 0 earned and not collected yet, on top of `amount_0`
+    pub unclaimed_fee_0: Amount,
+    /// Trading fees of token 1 earned and not collected yet, on top of `amount_1`
+    pub unclaimed_fee_1: Amount,
+    /// Reward emissions earned and not collected yet, one for each reward token of the pool
+    pub rewards: Vec<RewardAmount>,
+}
+
+/// A pending reward of a `PositionReport`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct RewardAmount {
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub mint: Pubkey,
+    pub amount: Amount,
+}
+
+/// How readily an asset can be spent, to tell liquid assets from encumbered ones in totals
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AssetClass {
+    /// Spendable right away, e.g. SOL and token balances
+    Liquid,
+    /// Provided as liquidity in an LP position
+    Lp,
+}
+
+/// An asset of a wallet, as in a row of `WalletReport::write_csv`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Holding {
+    pub class: AssetClass,
+    /// The kind of asset, as in CSV rows, e.g. "sol" or "lp_token_0"
+    pub asset: &'static str,
+    /// The mint of the asset, `None` for native SOL
+    #[serde(serialize_with = "serialize_optional_pubkey")]
+    pub mint: Option<Pubkey>,
+    /// The position PDA if the asset is in an LP position
+    #[serde(serialize_with = "serialize_optional_pubkey")]
+    pub position: Option<Pubkey>,
+    pub amount: Amount,
+}
+
+/// Total of an asset class of a mint, as amounts of different mints cannot be added up
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ClassTotal {
+    pub class: AssetClass,
+    /// The mint of the asset, `None` for native SOL
+    #[serde(serialize_with = "serialize_optional_pubkey")]
+    pub mint: Option<Pubkey>,
+    pub amount: Amount,
+}
+
+/// A balance of a wallet that changed between two reports
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct AmountChange {
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub address: Pubkey,
+    /// The kind of asset, as in CSV rows, e.g. "sol" or "lp_token_0"
+    pub asset: &'static str,
+    pub before: Amount,
+    pub after: Amount,
+}
+
+/// Positions of a wallet with the same tick range in a pool, and their amounts consolidated
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct DuplicateRange {
+    pub tick_lower_index: i32,
+    pub tick_upper_index: i32,
+    /// The position PDAs
+    #[serde(serialize_with = "serialize_pubkeys")]
+    pub positions: Vec<Pubkey>,
+    pub amount_0: Amount,
+    pub amount_1: Amount,
+}
+
+/// Group positions by tick range, keeping the ranges held by more than one position
+pub fn duplicate_ranges(positions: &[PositionReport]) -> Result<Vec<DuplicateRange>> {
+    let mut ranges: BTreeMap<(i32, i32), Vec<&PositionReport>> = BTreeMap::new();
+    for position in positions {
+        ranges.entry((position.tick_lower_index, position.tick_upper_index)).or_default().push(position);
+    }
+    ranges
+        .into_iter()
+        .filter(|(_, positions)| positions.len() > 1)
+        .map(|((tick_lower_index, tick_upper_index), positions)| {
+            Ok(DuplicateRange {
+                tick_lower_index,
+                tick_upper_index,
+                positions: positions.iter().map(|p| p.position).collect(),
+                amount_0: sum_amounts(positions.iter().map(|p| p.amount_0), positions[0].amount_0.decimals)?,
+                amount_1: sum_amounts(positions.iter().map(|p| p.amount_1), positions[0].amount_1.decimals)?,
+            })
+        })
+        .collect()
+}
+
+/// A raw token amount and the decimals to display it with
+///
+/// It serializes to the raw amount, the decimals and the exact amount in whole tokens as a string, like the RPC's
+/// `uiAmountString`, so that consumers never need float math: `{"raw": 1500000000, "decimals": 9,
+/// "ui_amount_string": "1.5"}`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Amount {
+    pub amount: u64,
+    pub decimals: u8,
+}
+
+impl Serialize for Amount {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("Amount", 3)?;
+        state.serialize_field("raw", &self.amount)?;
+        state.serialize_field("decimals", &self.decimals)?;
+        state.serialize_field("ui_amount_string", &self.ui_amount_string())?;
+        state.end()
+    }
+}
+
+impl Amount {
+    /// Add an amount of the same token, failing rather than mixing up units if the decimals differ
+    pub fn checked_add(&self, other: &Amount) -> Result<Amount> {
+        if self.decimals != other.decimals {
+            return Err(BalanceFetcherError::Math(format!(
+                "cannot add an amount with {} decimals to one with {} decimals",
+                other.decimals, self.decimals
+            )));
+        }
+        let amount = self
+            .amount
+            .checked_add(other.amount)
+            .ok_or_else(|| BalanceFetcherError::Math("amount overflows u64".to_string()))?;
+        Ok(Amount { amount, decimals: self.decimals })
+    }
+
+    /// The amount in whole tokens, lossy for large amounts
+    pub fn ui_amount(&self) -> f64 {
+        self.amount as f64 / 10u64.pow(self.decimals as u32) as f64
+    }
+
+    /// The amount in whole tokens as an exact decimal string, e.g. "1.5" for 1500000000 with 9 decimals
+    pub fn ui_amount_string(&self) -> String {
+        let decimals = self.decimals as usize;
+        let digits = format!("{:0>width$}", self.amount, width = decimals + 1);
+        let (integer, fraction) = digits.split_at(digits.len() - decimals);
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.is_empty() {
+            integer.to_string()
+        } else {
+            format!("{}.{}", integer, fraction)
+        }
+    }
+}
+
+impl WalletReport {
+    /// Total token 0 amount of the positions, failing on overflow
+    pub fn total_amount_0(&self) -> Result<Amount> {
+        sum_amounts(self.positions.iter().map(|p| p.amount_0), self.position_decimals().0)
+    }
+
+    /// Total token 1 amount of the positions, failing on overflow
+    pub fn total_amount_1(&self) -> Result<Amount> {
+        sum_amounts(self.positions.iter().map(|p| p.amount_1), self.position_decimals().1)
+    }
+
+    /// Total unclaimed fees of token 0 and token 1 of the positions, failing on overflow
+    pub fn total_unclaimed_fees(&self) -> Result<(Amount, Amount)> {
+        let (decimals_0, decimals_1) = self.position_decimals();
+        Ok((
+            sum_amounts(self.positions.iter().map(|p| p.unclaimed_fee_0), decimals_0)?,
+            sum_amounts(self.positions.iter().map(|p| p.unclaimed_fee_1), decimals_1)?,
+        ))
+    }
+
+    /// Pending rewards of all positions by reward mint, in the pool's order of its reward tokens
+    pub fn total_rewards(&self) -> Result<Vec<RewardAmount>> {
+        let mut totals: Vec<RewardAmount> = Vec::new();
+        for reward in self.positions.iter().flat_map(|p| &p.rewards) {
+            match totals.iter_mut().find(|total| total.mint == reward.mint) {
+                Some(total) => total.amount = total.amount.checked_add(&reward.amount)?,
+                None => totals.push(reward.clone()),
+            }
+        }
+        Ok(totals)
+    }
+
+    /// Decimals of token 0 and token 1 of the positions, 0 if there is no position to tell them from
+    pub fn position_decimals(&self) -> (u8, u8) {
+        self.positions
+            .first()
+            .map(|p| (p.amount_0.decimals, p.amount_1.decimals))
+            .unwrap_or_default()
+    }
+
+    /// The balances that changed since `previous`, an earlier report of the same wallet
+    pub fn changes_since(&self, previous: &WalletReport) -> Result<Vec<AmountChange>> {
+        let (decimals_0, decimals_1) =
+            if self.positions.is_empty() { previous.position_decimals() } else { self.position_decimals() };
+        // A wallet without positions has no decimals to tell, so take the other report's
+        let lp_amounts = |report: &WalletReport| -> Result<(Amount, Amount)> {
+            Ok((
+                Amount { amount: report.total_amount_0()?.amount, decimals: decimals_0 },
+                Amount { amount: report.total_amount_1()?.amount, decimals: decimals_1 },
+            ))
+        };
+        let (before_0, before_1) = lp_amounts(previous)?;
+        let (after_0, after_1) = lp_amounts(self)?;
+        Ok([
+            ("sol", previous.sol, self.sol),
+            ("wsol", previous.wsol, self.wsol),
+            ("sol_unified", previous.sol_unified, self.sol_unified),
+            ("lp_token_0", before_0, after_0),
+            ("lp_token_1", before_1, after_1),
+        ]
+        .into_iter()
+        .filter(|(_, before, after)| before != after)
+        .map(|(asset, before, after)| AmountChange { address: self.address, asset, before, after })
+        .collect())
+    }
+
+    /// The assets of the wallet with their class, SOL, WSOL and both tokens of each position
+    ///
+    /// The unified SOL balance is left out so that the holdings can be summed up.
+    pub fn holdings(&self) -> Vec<Holding> {
+        let wsol_mint_address = Pubkey::from_str(WSOL_MINT_ADDRESS).unwrap();
+        let liquid = |asset, mint, amount| Holding { class: AssetClass::Liquid, asset, mint, position: None, amount };
+        let mut holdings = vec![liquid("sol", None, self.sol), liquid("wsol", Some(wsol_mint_address), self.wsol)];
+        for p in &self.positions {
+            for (asset, mint, amount) in [("lp_token_0", p.mint_0, p.amount_0), ("lp_token_1", p.mint_1, p.amount_1)] {
+                let position = Some(p.position);
+                holdings.push(Holding { class: AssetClass::Lp, asset, mint: Some(mint), position, amount });
+            }
+        }
+        holdings
+    }
+
+    /// Write one CSV row per holding, without the header
+    pub fn write_csv<W: Write>(&self, writer: &mut W) -> Result<()> {
+        for holding in self.holdings() {
+            write_csv_row(
+                writer,
+                &self.address,
+                holding.asset,
+                holding.mint.as_ref(),
+                holding.position.as_ref(),
+                &holding.amount,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Write a CSV row in the format of `CSV_HEADER`
+///
+/// # Arguments
+/// - `writer` - Where to write the row
+/// - `address` - The address holding the asset
+/// - `asset` - The kind of asset, e.g. "sol" or "lp_token_0"
+/// - `mint` - The mint of the asset, `None` for native SOL
+/// - `position` - The position PDA if the asset is in an LP position
+/// - `amount` - The amount of the asset
+pub fn write_csv_row<W: Write>(
+    writer: &mut W,
+    address: &Pubkey,
+    asset: &str,
+    mint: Option<&Pubkey>,
+    position: Option<&Pubkey>,
+    amount: &Amount,
+) -> Result<()> {
+    // None of the fields can contain a comma or quote, so they need no escaping
+    writeln!(
+        writer,
+        "{},{},{},{},{},{},{}",
+        address,
+        asset,
+        mint.map(Pubkey::to_string).unwrap_or_default(),
+        position.map(Pubkey::to_string).unwrap_or_default(),
+        amount.amount,
+        amount.decimals,
+        amount.ui_amount_string(),
+    )?;
+    Ok(())
+}
+
+impl BalanceFetcher {
+    /// Fetch a report of the SOL balances of a wallet and its LP positions in Raydium SOL-USDC.1bp pool
+    ///
+    /// # Arguments
+    /// - `wallet_address` - The wallet address
+    ///
+    /// # Returns
+    /// - `WalletReport` - The balances and positions of the wallet
+    pub fn wallet_report_sol_usdc_1bp(&self, wallet_address: &Pubkey) -> Result<WalletReport> {
+        self.wallet_report(wallet_address, &PoolId::sol_usdc_1bp())
+    }
+
+    /// Fetch a report of the SOL balances of a wallet and its LP positions in a Raydium pool
+    ///
+    /// # Arguments
+    /// - `wallet_address` - The wallet address
+    /// - `pool_id` - The pool ID
+    ///
+    /// # Returns
+    /// - `WalletReport` - The balances and positions of the wallet
+    pub fn wallet_report(&self, wallet_address: &Pubkey, pool_id: &PoolId) -> Result<WalletReport> {
+        Ok(self.wallet_reports(&[*wallet_address], pool_id)?.remove(0))
+    }
+
+    /// Same as `wallet_report` for many wallets, batching account lookups across them
+    ///
+    /// Only the position NFTs are looked up wallet by wallet, as `getTokenAccountsByOwner` takes a single owner.
+    ///
+    /// # Arguments
+    /// - `wallet_addresses` - The wallet addresses
+    /// - `pool_id` - The pool ID
+    ///
+    /// # Returns
+    /// - `Vec<WalletReport>` - The balances and positions of each wallet, in the order of `wallet_addresses`
+    pub fn wallet_reports(&self, wallet_addresses: &[Pubkey], pool_id: &PoolId) -> Result<Vec<WalletReport>> {
+        let (slot, wallet_accounts) = self.get_multiple_accounts_chunked(wallet_addresses)?;
+        let wsol_mint_address = MintAddress::wsol();
+        let wsol_addresses: Vec<Pubkey> = wallet_addresses
+            .iter()
+            .map(|wallet| get_associated_token_address(wallet, &wsol_mint_address))
+            .collect();
+        let (_, wsol_accounts) = self.get_multiple_accounts_chunked(&wsol_addresses)?;
+        let wsol_decimals = self.mint_info(&wsol_mint_address)?.decimals;
+
+        let pool_account = self.get_account_cached(pool_id, AccountKind::Pool)?;
+        let pool_state = deserialize_anchor_account::<PoolState>(&pool_account)?;
+        let wallet_pdas = wallet_addresses
+            .iter()
+            .map(|wallet| self.wallet_position_pdas(wallet))
+            .collect::<Result<Vec<_>>>()?;
+        let (_, position_accounts) = self.get_multiple_accounts_chunked(&wallet_pdas.concat())?;
+        let mut position_accounts = position_accounts.into_iter();
+        let wallet_positions: Vec<_> = wallet_pdas
+            .iter()
+            .map(|pdas| {
+                let accounts = position_accounts.by_ref().take(pdas.len()).collect();
+                pool_positions_from_accounts(pdas, accounts, pool_id)
+            })
+            .collect();
+        // The tick arrays for fees and rewards are shared by many positions, so they are fetched for all wallets at
+        // once
+        let tick_states = self.positions_tick_states(pool_id, &pool_state, &wallet_positions.concat())?;
+        let mut tick_states = tick_states.into_iter();
+        let reward_infos = pool_state.reward_infos;
+        let reward_mints: Vec<Pubkey> = reward_infos
+            .iter()
+            .map(|reward_info| reward_info.token_mint)
+            .filter(|mint| *mint != Pubkey::default())
+            .collect();
+        let reward_decimals = reward_mints
+            .iter()
+            .map(|mint| Ok((*mint, self.mint_info(&MintAddress::from(*mint))?.decimals)))
+            .collect::<Result<HashMap<_, _>>>()?;
+        let timestamp = unix_timestamp();
+
+        let mut reports = Vec::with_capacity(wallet_addresses.len());
+        for (((wallet_address, wallet_account), wsol_account), pool_positions) in
+            wallet_addresses.iter().zip(wallet_accounts).zip(wsol_accounts).zip(wallet_positions)
+        {
+            // A wallet without account yet has no SOL, like one without WSOL account has no WSOL
+            let sol = wallet_account.map_or(0, |account| account.lamports);
+            let wsol = match wsol_account {
+                Some(account) => {
+                    StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data)?.base.amount
+                }
+                None => 0,
+            };
+            let positions = pool_positions
+                .into_iter()
+                .zip(tick_states.by_ref())
+                .map(|((position, state), (lower, upper))| {
+                    position_report(position, &state, &pool_state, &lower, &upper, &reward_decimals, timestamp)
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let duplicate_ranges = duplicate_ranges(&positions)?;
+            for range in &duplicate_ranges {
+                log::warn!(
+                    "{} positions of wallet {} share the tick range [{}, {}) in pool {}",
+                    range.positions.len(),
+                    wallet_address,
+                    range.tick_lower_index,
+                    range.tick_upper_index,
+                    pool_id,
+                );
+            }
+            let sol = Amount { amount: sol, decimals: SOL_DECIMALS };
+            let wsol = Amount { amount: wsol, decimals: wsol_decimals };
+            reports.push(WalletReport {
+                address: *wallet_address,
+                slot,
+                sol,
+                wsol,
+                sol_unified: sol.checked_add(&wsol)?,
+                pool_id: pool_id.pubkey(),
+                positions,
+                duplicate_ranges,
+            });
+        }
+        Ok(reports)
+    }
+}
+
+/// Report a position at the current price of its pool, with its unclaimed fees and pending rewards
+///
+/// # Arguments
+/// - `position` - The position PDA
+/// - `state` - The position state
+/// - `pool_state` - The state of the position's pool
+/// - `tick_lower` - The state of the position's lower tick
+/// - `tick_upper` - The state of the position's upper tick
+/// - `reward_decimals` - Decimals of the pool's reward mints
+/// - `timestamp` - The Unix timestamp to bring reward emissions up to date with
+pub(crate) fn position_report(
+    position: Pubkey,
+    state: &PersonalPositionState,
+    pool_state: &PoolState,
+    tick_lower: &TickState,
+    tick_upper: &TickState,
+    reward_decimals: &HashMap<Pubkey, u8>,
+    timestamp: u64,
+) -> Result<PositionReport> {
+    // PoolState is packed, copy fields out before use
+    let (mint_0, decimals_0) = (pool_state.token_mint_0, pool_state.mint_decimals_0);
+    let (mint_1, decimals_1) = (pool_state.token_mint_1, pool_state.mint_decimals_1);
+    let tick_current = pool_state.tick_current;
+    // Amounts at the current price, all of token 0 below the range and all of token 1 above it
+    let amounts = math::position_amounts(state, pool_state)?;
+    let (fee_0, fee_1) = math::unclaimed_fees(state, pool_state, tick_lower, tick_upper)?;
+    let rewards = math::pending_rewards(state, pool_state, tick_lower, tick_upper, timestamp)?
+        .into_iter()
+        .map(|reward| RewardAmount {
+            mint: reward.mint,
+            amount: Amount { amount: reward.amount, decimals: reward_decimals[&reward.mint] },
+        })
+        .collect();
+    Ok(PositionReport {
+        position,
+        nft_mint: state.nft_mint,
+        tick_lower_index: state.tick_lower_index,
+        tick_upper_index: state.tick_upper_index,
+        in_range: state.tick_lower_index <= tick_current && tick_current < state.tick_upper_index,
+        mint_0,
+        amount_0: Amount { amount: amounts.amount_0, decimals: decimals_0 },
+        mint_1,
+        amount_1: Amount { amount: amounts.amount_1, decimals: decimals_1 },
+        unclaimed_fee_0: Amount { amount: fee_0, decimals: decimals_0 },
+        unclaimed_fee_1: Amount { amount: fee_1, decimals: decimals_1 },
+        rewards,
+    })
+}
+
+/// Balances and positions of several wallets, along with their totals
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct CombinedReport {
+    pub wallets: Vec<WalletReport>,
+    pub total: TotalReport,
+}
+
+/// Totals of `CombinedReport` over all wallets
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TotalReport {
+    pub sol: Amount,
+    pub wsol: Amount,
+    pub sol_unified: Amount,
+    pub positions: usize,
+    /// Token 0 in the positions of all wallets
+    pub amount_0: Amount,
+    /// Token 1 in the positions of all wallets
+    pub amount_1: Amount,
+    /// Holdings of all wallets per asset class and mint
+    pub classes: Vec<ClassTotal>,
+}
+
+impl CombinedReport {
+    /// Combine reports of wallets on the same pool
+    ///
+    /// Fails if amounts of the same asset have different decimals, which would make their total meaningless.
+    pub fn new(wallets: Vec<WalletReport>) -> Result<Self> {
+        let position_decimals = wallets
+            .iter()
+            .find(|w| !w.positions.is_empty())
+            .map(WalletReport::position_decimals)
+            .unwrap_or_default();
+        let positions = || wallets.iter().flat_map(|w| &w.positions);
+        let total = TotalReport {
+            sol: sum_amounts(wallets.iter().map(|w| w.sol), SOL_DECIMALS)?,
+            wsol: sum_amounts(wallets.iter().map(|w| w.wsol), SOL_DECIMALS)?,
+            sol_unified: sum_amounts(wallets.iter().map(|w| w.sol_unified), SOL_DECIMALS)?,
+            positions: positions().count(),
+            amount_0: sum_amounts(positions().map(|p| p.amount_0), position_decimals.0)?,
+            amount_1: sum_amounts(positions().map(|p| p.amount_1), position_decimals.1)?,
+            classes: class_totals(&wallets)?,
+        };
+        Ok(Self { wallets, total })
+    }
+}
+
+/// Sum amounts of the same token with `decimals`
+fn sum_amounts(amounts: impl IntoIterator<Item = Amount>, decimals: u8) -> Result<Amount> {
+    amounts.into_iter().try_fold(Amount { amount: 0, decimals }, |total, amount| total.checked_add(&amount))
+}
+
+/// Sum the holdings of the wallets per asset class and mint, ordered by both
+fn class_totals(wallets: &[WalletReport]) -> Result<Vec<ClassTotal>> {
+    let mut totals: BTreeMap<(AssetClass, Option<Pubkey>), Amount> = BTreeMap::new();
+    for holding in wallets.iter().flat_map(WalletReport::holdings) {
+        let total = totals
+            .entry((holding.class, holding.mint))
+            .or_insert(Amount { amount: 0, decimals: holding.amount.decimals });
+        *total = total.checked_add(&holding.amount)?;
+    }
+    Ok(totals.into_iter().map(|((class, mint), amount)| ClassTotal { class, mint, amount }).collect())
+}
+
+/// Distribution of SOL balances and position amounts over wallets, e.g. to analyze a cohort of user wallets
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct StatsReport {
+    pub wallets: usize,
+    pub sol_unified: AmountStats,
+    /// Token 0 in the positions of each wallet
+    pub lp_token_0: AmountStats,
+    /// Token 1 in the positions of each wallet
+    pub lp_token_1: AmountStats,
+}
+
+/// Percentiles and histogram of an amount over wallets
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct AmountStats {
+    pub min: Amount,
+    pub median: Amount,
+    pub p95: Amount,
+    pub max: Amount,
+    /// Wallets per order of magnitude of the raw amount, from the smallest amount to the largest
+    pub histogram: Vec<HistogramBucket>,
+}
+
+/// Number of wallets with an amount in `[lower, upper)`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct HistogramBucket {
+    pub lower: Amount,
+    pub upper: Amount,
+    pub wallets: usize,
+}
+
+impl StatsReport {
+    /// Summarize reports of wallets on the same pool, failing if the positions of a wallet overflow
+    pub fn new(wallets: &[WalletReport]) -> Result<Self> {
+        let (decimals_0, decimals_1) = wallets
+            .iter()
+            .find(|w| !w.positions.is_empty())
+            .map(WalletReport::position_decimals)
+            .unwrap_or_default();
+        let amounts = |f: fn(&WalletReport) -> Result<Amount>| {
+            wallets.iter().map(|w| Ok(f(w)?.amount)).collect::<Result<Vec<_>>>()
+        };
+        Ok(Self {
+            wallets: wallets.len(),
+            sol_unified: AmountStats::new(wallets.iter().map(|w| w.sol_unified.amount).collect(), SOL_DECIMALS),
+            lp_token_0: AmountStats::new(amounts(WalletReport::total_amount_0)?, decimals_0),
+            lp_token_1: AmountStats::new(amounts(WalletReport::total_amount_1)?, decimals_1),
+        })
+    }
+}
+
+impl AmountStats {
+    /// Percentiles are nearest-rank, i.e. always one of the amounts. All zero if there is no amount.
+    pub fn new(mut amounts: Vec<u64>, decimals: u8) -> Self {
+        amounts.sort_unstable();
+        let amount = |amount: u64| Amount { amount, decimals };
+
+        let mut histogram: Vec<HistogramBucket> = Vec::new();
+        let zeros = amounts.iter().take_while(|&&a| a == 0).count();
+        if zeros > 0 {
+            histogram.push(HistogramBucket { lower: amount(0), upper: amount(1), wallets: zeros });
+        }
+        if let (Some(&smallest), Some(&largest)) = (amounts.get(zeros), amounts.last()) {
+            for magnitude in smallest.ilog10()..=largest.ilog10() {
+                let lower = 10u64.pow(magnitude);
+                let upper = 10u64.checked_pow(magnitude + 1).unwrap_or(u64::MAX);
+                let wallets = amounts.iter().filter(|&&a| lower <= a && a < upper).count();
+                histogram.push(HistogramBucket { lower: amount(lower), upper: amount(upper), wallets });
+            }
+        }
+
+        Self {
+            min: amount(amounts.first().copied().unwrap_or_default()),
+            median: amount(percentile(amounts.clone(), 0.5)),
+            p95: amount(percentile(amounts.clone(), 0.95)),
+            max: amount(amounts.last().copied().unwrap_or_default()),
+            histogram,
+        }
+    }
+}
+
+/// Serialize a pubkey as its base58 string, rather than the bytes `Pubkey` serializes to
+pub(crate) fn serialize_pubkey<S>(pubkey: &Pubkey, serializer: S) -> std::result::Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_str(pubkey)
+}
+
+fn serialize_optional_pubkey<S>(pubkey: &Option<Pubkey>, serializer: S) -> std::result::Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match pubkey {
+        Some(pubkey) => serializer.collect_str(pubkey),
+        None => serializer.serialize_none(),
+    }
+}
+
+pub(crate) fn serialize_pubkeys<S>(pubkeys: &[Pubkey], serializer: S) -> std::result::Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_seq(pubkeys.iter().map(Pubkey::to_string))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::balance_fetcher::SOL_USDC_1BP_POOL_ID;
+
+    #[test]
+    fn test_wallet_report_json() {
+        let mint = Pubkey::from_str(WSOL_MINT_ADDRESS).unwrap();
+        let report = WalletReport {
+            address: mint,
+            slot: 1,
+            sol: Amount { amount: 1_500_000_000, decimals: 9 },
+            wsol: Amount { amount: 0, decimals: 9 },
+            sol_unified: Amount { amount: 1_500_000_000, decimals: 9 },
+            pool_id: Pubkey::from_str(SOL_USDC_1BP_POOL_ID).unwrap(),
+            positions: vec![],
+            duplicate_ranges: vec![],
+        };
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["address"], WSOL_MINT_ADDRESS);
+        assert_eq!(json["pool_id"], SOL_USDC_1BP_POOL_ID);
+        assert_eq!(json["sol"]["raw"], 1_500_000_000u64);
+        assert_eq!(json["sol"]["decimals"], 9);
+        assert_eq!(json["sol"]["ui_amount_string"], "1.5");
+        assert_eq!(report.sol.ui_amount(), 1.5);
+    }
+
+    #[test]
+    fn test_ui_amount_string() {
+        assert_eq!(Amount { amount: 1_500_000_000, decimals: 9 }.ui_amount_string(), "1.5");
+        assert_eq!(Amount { amount: 1, decimals: 6 }.ui_amount_string(), "0.000001");
+        assert_eq!(Amount { amount: 0, decimals: 9 }.ui_amount_string(), "0");
+        assert_eq!(Amount { amount: 42, decimals: 0 }.ui_amount_string(), "42");
+        assert_eq!(Amount { amount: u64::MAX, decimals: 9 }.ui_amount_string(), "18446744073.709551615");
+    }
+
+    #[test]
+    fn test_wallet_report_csv() {
+        let address = Pubkey::from_str("53zSj4G935ZY2a5x2UnGAiJXSuXXmGHaLph2zhAUvYpg").unwrap();
+        let report = WalletReport {
+            address,
+            slot: 1,
+            sol: Amount { amount: 1_500_000_000, decimals: 9 },
+            wsol: Amount { amount: 0, decimals: 9 },
+            sol_unified: Amount { amount: 1_500_000_000, decimals: 9 },
+            pool_id: Pubkey::from_str(SOL_USDC_1BP_POOL_ID).unwrap(),
+            positions: vec![],
+            duplicate_ranges: vec![],
+        };
+        let mut csv = Vec::new();
+        report.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0], format!("{},sol,,,1500000000,9,1.5", address));
+        assert_eq!(rows[1], format!("{},wsol,{},,0,9,0", address, WSOL_MINT_ADDRESS));
+        assert_eq!(rows[0].split(',').count(), CSV_HEADER.split(',').count());
+    }
+
+    #[test]
+    fn test_combined_report() {
+        let report = |sol| WalletReport {
+            address: Pubkey::new_unique(),
+            slot: 1,
+            sol: Amount { amount: sol, decimals: 9 },
+            wsol: Amount { amount: 1, decimals: 9 },
+            sol_unified: Amount { amount: sol + 1, decimals: 9 },
+            pool_id: Pubkey::from_str(SOL_USDC_1BP_POOL_ID).unwrap(),
+            positions: vec![],
+            duplicate_ranges: vec![],
+        };
+        let combined = CombinedReport::new(vec![report(10), report(20)]).unwrap();
+        assert_eq!(combined.total.sol.amount, 30);
+        assert_eq!(combined.total.wsol.amount, 2);
+        assert_eq!(combined.total.sol_unified.amount, 32);
+        assert_eq!(combined.total.positions, 0);
+        let wsol = Pubkey::from_str(WSOL_MINT_ADDRESS).unwrap();
+        let classes: Vec<(AssetClass, Option<Pubkey>, u64)> =
+            combined.total.classes.iter().map(|total| (total.class, total.mint, total.amount.amount)).collect();
+        assert_eq!(classes, vec![(AssetClass::Liquid, None, 30), (AssetClass::Liquid, Some(wsol), 2)]);
+        let json = serde_json::to_value(&combined.total.classes).unwrap();
+        assert_eq!(json[0]["class"], "liquid");
+        assert_eq!(json[0]["mint"], serde_json::Value::Null);
+        assert_eq!(json[1]["mint"], WSOL_MINT_ADDRESS);
+
+        let mut other_decimals = report(30);
+        other_decimals.wsol.decimals = 6;
+        assert!(CombinedReport::new(vec![report(10), other_decimals]).is_err());
+    }
+
+    #[test]
+    fn test_amount_checked_add() {
+        let sol = Amount { amount: 2, decimals: 9 };
+        assert_eq!(sol.checked_add(&Amount { amount: 3, decimals: 9 }).unwrap(), Amount { amount: 5, decimals: 9 });
+        let usdc = Amount { amount: 3, decimals: 6 };
+        assert!(matches!(sol.checked_add(&usdc), Err(BalanceFetcherError::Math(_))));
+        assert!(Amount { amount: u64::MAX, decimals: 9 }.checked_add(&sol).is_err());
+    }
+
+    #[test]
+    fn test_amount_stats() {
+        let amounts: Vec<u64> = (1..=20).chain([0, 0, 5_000]).collect();
+        let stats = AmountStats::new(amounts, 9);
+        assert_eq!(stats.min.amount, 0);
+        assert_eq!(stats.median.amount, 10);
+        assert_eq!(stats.p95.amount, 20);
+        assert_eq!(stats.max.amount, 5_000);
+        let buckets: Vec<(u64, u64, usize)> =
+            stats.histogram.iter().map(|b| (b.lower.amount, b.upper.amount, b.wallets)).collect();
+        assert_eq!(buckets, vec![(0, 1, 2), (1, 10, 9), (10, 100, 11), (100, 1_000, 0), (1_000, 10_000, 1)]);
+
+        let empty = AmountStats::new(vec![], 9);
+        assert_eq!(empty.median.amount, 0);
+        assert!(empty.histogram.is_empty());
+    }
+
+    #[test]
+    fn test_changes_since() {
+        let report = |sol, wsol| WalletReport {
+            address: Pubkey::from_str("53zSj4G935ZY2a5x2UnGAiJXSuXXmGHaLph2zhAUvYpg").unwrap(),
+            slot: 1,
+            sol: Amount { amount: sol, decimals: 9 },
+            wsol: Amount { amount: wsol, decimals: 9 },
+            sol_unified: Amount { amount: sol + wsol, decimals: 9 },
+            pool_id: Pubkey::from_str(SOL_USDC_1BP_POOL_ID).unwrap(),
+            positions: vec![],
+            duplicate_ranges: vec![],
+        };
+        assert!(report(10, 5).changes_since(&report(10, 5)).unwrap().is_empty());
+        let changes = report(12, 5).changes_since(&report(10, 5)).unwrap();
+        let assets: Vec<&str> = changes.iter().map(|c| c.asset).collect();
+        assert_eq!(assets, vec!["sol", "sol_unified"]);
+        assert_eq!(changes[0].before.amount, 10);
+        assert_eq!(changes[0].after.amount, 12);
+    }
+
+    #[test]
+    fn test_duplicate_ranges() {
+        let position = |tick_lower_index, tick_upper_index, amount| PositionReport {
+            position: Pubkey::new_unique(),
+            nft_mint: Pubkey::new_unique(),
+            tick_lower_index,
+            tick_upper_index,
+            in_range: true,
+            mint_0: Pubkey::from_str(WSOL_MINT_ADDRESS).unwrap(),
+            amount_0: Amount { amount, decimals: 9 },
+            mint_1: Pubkey::new_unique(),
+            amount_1: Amount { amount, decimals: 6 },
+            unclaimed_fee_0: Amount { amount: 0, decimals: 9 },
+            unclaimed_fee_1: Amount { amount: 0, decimals: 6 },
+            rewards: vec![],
+        };
+        let positions = [position(-10, 10, 1), position(-10, 10, 2), position(-10, 20, 4), position(-10, 10, 8)];
+        let duplicates = duplicate_ranges(&positions).unwrap();
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!((duplicates[0].tick_lower_index, duplicates[0].tick_upper_index), (-10, 10));
+        assert_eq!(duplicates[0].positions, vec![positions[0].position, positions[1].position, positions[3].position]);
+        assert_eq!(duplicates[0].amount_0, Amount { amount: 11, decimals: 9 });
+        assert_eq!(duplicates[0].amount_1, Amount { amount: 11, decimals: 6 });
+    }
+
+    #[test]
+    fn test_position_report() {
+        let pool = PoolState {
+            sqrt_price_x64: math::sqrt_price_at_tick(0).unwrap(),
+            mint_decimals_0: 9,
+            mint_decimals_1: 6,
+            ..PoolState::default()
+        };
+        let ticks = TickState::default();
+        let report = |tick_lower_index, tick_upper_index| {
+            let state = PersonalPositionState {
+                tick_lower_index,
+                tick_upper_index,
+                liquidity: 1_000_000_000,
+                ..PersonalPositionState::default()
+            };
+            position_report(Pubkey::new_unique(), &state, &pool, &ticks, &ticks, &HashMap::new(), 0).unwrap()
+        };
+        let in_range = report(-100, 100);
+        assert!(in_range.in_range && in_range.amount_0.amount > 0 && in_range.amount_1.amount > 0);
+        assert_eq!(in_range.amount_0.decimals, 9);
+        // Above the current price the position holds token 0 only, below it token 1 only
+        let above = report(100, 200);
+        assert!(!above.in_range && above.amount_0.amount > 0);
+        assert_eq!(above.amount_1.amount, 0);
+        let below = report(-200, -100);
+        assert!(!below.in_range && below.amount_1.amount > 0);
+        assert_eq!(below.amount_0.amount, 0);
+    }
+
+    #[test]
+    fn test_total_amount_overflow() {
+        let position = PositionReport {
+            position: Pubkey::new_unique(),
+            nft_mint: Pubkey::new_unique(),
+            tick_lower_index: -10,
+            tick_upper_index: 10,
+            in_range: true,
+            mint_0: Pubkey::from_str(WSOL_MINT_ADDRESS).unwrap(),
+            amount_0: Amount { amount: u64::MAX, decimals: 9 },
+            mint_1: Pubkey::new_unique(),
+            amount_1: Amount { amount: 1, decimals: 6 },
+            unclaimed_fee_0: Amount { amount: 1, decimals: 9 },
+            unclaimed_fee_1: Amount { amount: u64::MAX, decimals: 6 },
+            rewards: vec![],
+        };
+        let report = WalletReport {
+            address: Pubkey::new_unique(),
+            slot: 1,
+            sol: Amount { amount: 0, decimals: 9 },
+            wsol: Amount { amount: 0, decimals: 9 },
+            sol_unified: Amount { amount: 0, decimals: 9 },
+            pool_id: Pubkey::from_str(SOL_USDC_1BP_POOL_ID).unwrap(),
+            positions: vec![position.clone(), position],
+            duplicate_ranges: vec![],
+        };
+        assert!(matches!(report.total_amount_0(), Err(BalanceFetcherError::Math(_))));
+        assert_eq!(report.total_amount_1().unwrap(), Amount { amount: 2, decimals: 6 });
+        assert!(matches!(report.total_unclaimed_fees(), Err(BalanceFetcherError::Math(_))));
+        assert!(duplicate_ranges(&report.positions).is_err());
+    }
+
+    #[test]
+    fn test_wallet_reports() {
+        let fetcher = BalanceFetcher::new("https://api.mainnet-beta.solana.com");
+        let wallets = [
+            Pubkey::from_str("53zSj4G935ZY2a5x2UnGAiJXSuXXmGHaLph2zhAUvYpg").unwrap(),
+            // Binance wallet address
+            Pubkey::from_str("5tzFkiKscXHK5ZXCGbXZxdw7gTjjD1mBwuoFbhUvuAi9").unwrap(),
+        ];
+        let pool_id = PoolId::sol_usdc_1bp();
+        let reports = fetcher.wallet_reports(&wallets, &pool_id).unwrap();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].address, wallets[0]);
+        assert!(!reports[0].positions.is_empty());
+        assert!(reports[1].sol.amount > 0);
+    }
+
+    #[test]
+    fn test_wallet_report_sol_usdc_1bp() {
+        let fetcher = BalanceFetcher::new("https://api.mainnet-beta.solana.com");
+        let wallet = Pubkey::from_str("53zSj4G935ZY2a5x2UnGAiJXSuXXmGHaLph2zhAUvYpg").unwrap();
+        let report = fetcher.wallet_report_sol_usdc_1bp(&wallet).unwrap();
+        assert!(report.slot > 0);
+        assert!(!report.positions.is_empty());
+        assert_eq!(report.positions[0].mint_0, Pubkey::from_str(WSOL_MINT_ADDRESS).unwrap());
+        assert_eq!(report.positions[0].amount_0.decimals, 9);
+        assert_eq!(report.positions[0].amount_1.decimals, 6);
+        assert_eq!(report.positions[0].unclaimed_fee_0.decimals, 9);
+    }
+}
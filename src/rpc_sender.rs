@@ -0,0 +1,319 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use rand::Rng;
+use reqwest::header::{CONTENT_TYPE, RETRY_AFTER};
+use reqwest::StatusCode;
+use solana_client::rpc_sender::{RpcSender, RpcTransportStats};
+use solana_rpc_client_api::client_error::{Error as ClientError, ErrorKind, Result as ClientResult};
+use serde::Deserialize;
+use solana_rpc_client_api::custom_error;
+use solana_rpc_client_api::request::{RpcError, RpcRequest, RpcResponseErrorData};
+use solana_rpc_client_api::response::RpcSimulateTransactionResult;
+use crate::connection_manager::ConnectionManager;
+use crate::rate_limiter::RateLimiter;
+
+/// How often and how long to wait before retrying an RPC request that failed transiently
+///
+/// The wait doubles after each attempt, from `base_delay` up to `max_delay`. A rate limited request waits as long
+/// as the `Retry-After` header of the response asks instead, if it is longer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts of a request in total, 1 disables retrying
+    pub max_attempts: u32,
+    /// Wait after the first failed attempt
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Wait a random 50% to 100% of the delay, so that clients failing together do not retry together
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    /// Up to 5 attempts, waiting 500ms, 1s, 2s and 4s with jitter
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Never retry
+    pub fn none() -> Self {
+        Self { max_attempts: 1, base_delay: Duration::ZERO, max_delay: Duration::ZERO, jitter: false }
+    }
+
+    /// Wait before the attempt following failed attempt `attempt`, counted from 1
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        if self.jitter {
+            delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+        } else {
+            delay
+        }
+    }
+}
+
+/// A failed attempt of a request, with the wait the server asked for if rate limited
+struct FailedAttempt {
+    error: ClientError,
+    retry_after: Option<Duration>,
+}
+
+impl From<ClientError> for FailedAttempt {
+    fn from(error: ClientError) -> Self {
+        Self { error, retry_after: None }
+    }
+}
+
+impl From<reqwest::Error> for FailedAttempt {
+    fn from(error: reqwest::Error) -> Self {
+        ClientError::from(error).into()
+    }
+}
+
+/// JSON-RPC over HTTP with a preconfigured HTTP client, e.g. with authentication headers, retries, a rate limit
+/// and failover between endpoints
+pub(crate) struct HttpRpcSender {
+    client: reqwest::Client,
+    connections: ConnectionManager,
+    retry_policy: RetryPolicy,
+    rate_limiter: Option<RateLimiter>,
+    request_id: AtomicU64,
+    stats: RwLock<RpcTransportStats>,
+}
+
+impl HttpRpcSender {
+    pub(crate) fn new(
+        client: reqwest::Client,
+        connections: ConnectionManager,
+        retry_policy: RetryPolicy,
+        rate_limiter: Option<RateLimiter>,
+    ) -> Self {
+        Self {
+            client,
+            connections,
+            retry_policy,
+            rate_limiter,
+            request_id: AtomicU64::new(0),
+            stats: RwLock::new(Default::default()),
+        }
+    }
+
+    async fn send_once(&self, url: &str, body: &str) -> Result<serde_json::Value, FailedAttempt> {
+        let response = self
+            .client
+            .post(url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .send()
+            .await?;
+        if response.status() == StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<u64>().ok())
+                .map(Duration::from_secs);
+            let error = ClientError::from(response.error_for_status().unwrap_err());
+            return Err(FailedAttempt { error, retry_after });
+        }
+        let response = response.error_for_status()?;
+        let mut json: serde_json::Value = response.json().await?;
+        if json["error"].is_object() {
+            return Err(ClientError::from(rpc_response_error(json["error"].take())).into());
+        }
+        Ok(json["result"].take())
+    }
+}
+
+/// The JSON-RPC error object of a response, as listed in the JSON-RPC 2.0 specification
+#[derive(Deserialize)]
+struct RpcErrorObject {
+    code: i64,
+    message: String,
+    #[serde(default)]
+    data: serde_json::Value,
+}
+
+/// Decode the error of a JSON-RPC response like `HttpSender` does, keeping the preflight simulation result of a
+/// rejected transaction and how far behind an unhealthy node is
+fn rpc_response_error(error: serde_json::Value) -> RpcError {
+    let RpcErrorObject { code, message, data } = match serde_json::from_value(error.clone()) {
+        Ok(error) => error,
+        Err(err) => {
+            return RpcError::RpcRequestError(format!("Failed to deserialize RPC error response: {} [{}]", error, err));
+        }
+    };
+    let data = match code {
+        custom_error::JSON_RPC_SERVER_ERROR_SEND_TRANSACTION_PREFLIGHT_FAILURE => {
+            match serde_json::from_value::<RpcSimulateTransactionResult>(data) {
+                Ok(data) => RpcResponseErrorData::SendTransactionPreflightFailure(data),
+                Err(err) => {
+                    log::debug!("failed to deserialize RpcSimulateTransactionResult: {:?}", err);
+                    RpcResponseErrorData::Empty
+                }
+            }
+        }
+        custom_error::JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY => {
+            match serde_json::from_value::<custom_error::NodeUnhealthyErrorData>(data) {
+                Ok(custom_error::NodeUnhealthyErrorData { num_slots_behind }) => {
+                    RpcResponseErrorData::NodeUnhealthy { num_slots_behind }
+                }
+                Err(_) => RpcResponseErrorData::Empty,
+            }
+        }
+        _ => RpcResponseErrorData::Empty,
+    };
+    RpcError::RpcResponseError { code, message, data }
+}
+
+#[async_trait]
+impl RpcSender for HttpRpcSender {
+    async fn send(&self, request: RpcRequest, params: serde_json::Value) -> ClientResult<serde_json::Value> {
+        let request_id = self.request_id.fetch_add(1, Ordering::Relaxed);
+        let body = request.build_request_json(request_id, params).to_string();
+        let mut attempt = 1;
+        loop {
+            // Retries count against the rate limit as well
+            if let Some(rate_limiter) = &self.rate_limiter {
+                rate_limiter.acquire().await;
+            }
+            let (endpoint, url) = self.connections.endpoint();
+            let start = Instant::now();
+            let result = self.send_once(url, &body).await;
+            {
+                let mut stats = self.stats.write().unwrap();
+                stats.request_count += 1;
+                stats.elapsed_time += start.elapsed();
+            }
+            let failed = match result {
+                Ok(result) => return Ok(result),
+                Err(failed) => failed,
+            };
+            if attempt >= self.retry_policy.max_attempts || !is_transient_error(&failed.error) {
+                return Err(failed.error);
+            }
+            // Another endpoint is tried right away, the same one after backing off
+            if is_failover_error(&failed.error) && self.connections.fail_over(endpoint) {
+                log::debug!("{} failed on {}, retrying on another endpoint: {}", request, url, failed.error);
+            } else {
+                let mut delay = self.retry_policy.backoff(attempt);
+                if let Some(retry_after) = failed.retry_after {
+                    delay = delay.max(retry_after);
+                    self.stats.write().unwrap().rate_limited_time += delay;
+                }
+                log::debug!("{} failed on attempt {}, retrying in {:?}: {}", request, attempt, delay, failed.error);
+                tokio::time::sleep(delay).await;
+            }
+            attempt += 1;
+        }
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.stats.read().unwrap().clone()
+    }
+
+    fn url(&self) -> String {
+        self.connections.endpoint().1.to_string()
+    }
+}
+
+/// Whether a request may succeed when retried: timeouts, connection failures, rate limiting, server errors and
+/// nodes too unhealthy or too far behind to serve the request
+fn is_transient_error(err: &ClientError) -> bool {
+    match &err.kind {
+        ErrorKind::Reqwest(err) => {
+            err.is_timeout()
+                || err.is_connect()
+                || err.status().map_or(false, |status| {
+                    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+                })
+        }
+        ErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => is_node_behind_code(*code),
+        _ => false,
+    }
+}
+
+/// Whether an endpoint seems down rather than the request being bad or rate limited: timeouts, connection
+/// failures, server errors and unhealthy nodes
+fn is_failover_error(err: &ClientError) -> bool {
+    match &err.kind {
+        ErrorKind::Reqwest(err) => {
+            err.is_timeout() || err.is_connect() || err.status().map_or(false, |status| status.is_server_error())
+        }
+        ErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => is_node_behind_code(*code),
+        _ => false,
+    }
+}
+
+/// Whether a JSON-RPC error code means the node is unhealthy or has not reached the slot asked for, which another
+/// node or a later attempt may not be
+fn is_node_behind_code(code: i64) -> bool {
+    code == custom_error::JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY
+        || code == custom_error::JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+            jitter: false,
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(4), Duration::from_millis(800));
+        assert_eq!(policy.backoff(5), Duration::from_secs(1));
+        assert_eq!(policy.backoff(100), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_rpc_response_error() {
+        let error = rpc_response_error(serde_json::json!({
+            "code": -32005,
+            "message": "Node is behind by 42 slots",
+            "data": { "numSlotsBehind": 42 },
+        }));
+        assert!(matches!(
+            &error,
+            RpcError::RpcResponseError {
+                code: -32005,
+                data: RpcResponseErrorData::NodeUnhealthy { num_slots_behind: Some(42) },
+                ..
+            }
+        ));
+        let error = ClientError::from(error);
+        assert!(is_transient_error(&error));
+        assert!(is_failover_error(&error));
+
+        let error = rpc_response_error(serde_json::json!({
+            "code": -32602,
+            "message": "Invalid param: could not find account",
+        }));
+        assert!(matches!(&error, RpcError::RpcResponseError { code: -32602, data: RpcResponseErrorData::Empty, .. }));
+        assert!(!is_transient_error(&ClientError::from(error)));
+
+        let error = rpc_response_error(serde_json::json!({ "message": "no code" }));
+        assert!(matches!(error, RpcError::RpcRequestError(_)));
+    }
+
+    #[test]
+    fn test_backoff_jitter() {
+        let policy = RetryPolicy { jitter: true, ..RetryPolicy::default() };
+        for _ in 0..100 {
+            let delay = policy.backoff(2);
+            assert!(delay >= policy.base_delay && delay <= policy.base_delay * 2);
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use raydium_amm_v3::libraries::{swap_math, tick_math};
+use crate::math;
+use crate::error::{BalanceFetcherError, Result};
+
+/// Direction of a swap in a pool
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SwapDirection {
+    /// Swap token 0 for token 1, which moves the pool price down
+    ZeroForOne,
+    /// Swap token 1 for token 0, which moves the pool price up
+    OneForZero,
+}
+
+/// Result of simulating an exact input swap against the current pool state
+#[derive(Clone, Debug, PartialEq)]
+pub struct SwapQuote {
+    /// Amount of input token paid, including fee
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee_amount: u64,
+    /// How much worse the execution price (fee included) is than the pool price before the swap, e.g. 0.01 is 1%
+    pub price_impact: f64,
+    pub sqrt_price_x64_after: u128,
+}
+
+/// An initialized tick, and the liquidity added (removed if negative) when the price crosses it upwards
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct InitializedTick {
+    pub tick: i32,
+    pub liquidity_net: i128,
+}
+
+/// Simulate an exact input swap with the CLMM swap math, stepping through initialized ticks
+///
+/// # Arguments
+/// - `sqrt_price_x64`, `tick_current`, `liquidity` - The pool state before the swap
+/// - `fee_rate` - The trade fee rate of the pool, in hundredths of a bip (1e-6)
+/// - `ticks` - Initialized ticks sorted by tick index
+/// - `tick_range` - The range of ticks `ticks` was collected from, the swap cannot move the price beyond it
+/// - `amount_in` - Amount of input token, including fee
+/// - `direction` - The direction of the swap
+///
+/// # Returns
+/// - `SwapQuote` - The output amount and price impact, or an error if `amount_in` cannot be filled within `tick_range`
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn quote_exact_input(
+    sqrt_price_x64: u128,
+    tick_current: i32,
+    liquidity: u128,
+    fee_rate: u32,
+    ticks: &[InitializedTick],
+    tick_range: (i32, i32),
+    amount_in: u64,
+    direction: SwapDirection,
+) -> Result<SwapQuote> {
+    let zero_for_one = direction == SwapDirection::ZeroForOne;
+    let block_timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as u32;
+    let (mut sqrt_price, mut tick, mut liquidity) = (sqrt_price_x64, tick_current, liquidity);
+    let mut amount_remaining = amount_in;
+    let (mut amount_out, mut fee_amount) = (0, 0);
+
+    while amount_remaining > 0 {
+        let next_tick = if zero_for_one {
+            ticks.iter().rev().find(|t| t.tick <= tick)
+        } else {
+            ticks.iter().find(|t| t.tick > tick)
+        };
+        let target_tick = match next_tick {
+            Some(t) => t.tick,
+            None if zero_for_one => tick_range.0,
+            None => tick_range.1,
+        }
+        .clamp(tick_math::MIN_TICK, tick_math::MAX_TICK);
+        let sqrt_price_target = tick_math::get_sqrt_price_at_tick(target_tick)?;
+
+        let step = swap_math::compute_swap_step(
+            sqrt_price,
+            sqrt_price_target,
+            liquidity,
+            amount_remaining,
+            fee_rate,
+            true,
+            zero_for_one,
+            block_timestamp,
+        )?;
+        amount_remaining -= step.amount_in + step.fee_amount;
+        amount_out += step.amount_out;
+        fee_amount += step.fee_amount;
+        sqrt_price = step.sqrt_price_next_x64;
+        if amount_remaining == 0 || sqrt_price != sqrt_price_target {
+            break;
+        }
+
+        let Some(next_tick) = next_tick else {
+            return Err(BalanceFetcherError::Math(format!(
+                "amount {} is too large to quote, only {} can be filled within the fetched tick arrays",
+                amount_in,
+                amount_in - amount_remaining
+            )));
+        };
+        let liquidity_net = if zero_for_one { -next_tick.liquidity_net } else { next_tick.liquidity_net };
+        liquidity = liquidity
+            .checked_add_signed(liquidity_net)
+            .ok_or_else(|| {
+                BalanceFetcherError::Math(format!("liquidity overflow when crossing tick {}", next_tick.tick))
+            })?;
+        tick = if zero_for_one { next_tick.tick - 1 } else { next_tick.tick };
+    }
+
+    let amount_in = amount_in - amount_remaining;
+    // Prices are token 1 per token 0 in raw amounts
+    let price_before = math::sqrt_price_x64_to_price(sqrt_price_x64, 0, 0);
+    let price_impact = if amount_in == 0 || amount_out == 0 {
+        if amount_in == 0 { 0.0 } else { 1.0 }
+    } else if zero_for_one {
+        1.0 - (amount_out as f64 / amount_in as f64) / price_before
+    } else {
+        1.0 - price_before / (amount_in as f64 / amount_out as f64)
+    };
+    Ok(SwapQuote {
+        amount_in,
+        amount_out,
+        fee_amount,
+        price_impact,
+        sqrt_price_x64_after: sqrt_price,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_exact_input_within_single_range() {
+        // Price 1.0 at tick 0, liquidity active from tick -600 to 600
+        let sqrt_price_x64 = tick_math::get_sqrt_price_at_tick(0).unwrap();
+        let ticks = [
+            InitializedTick { tick: -600, liquidity_net: 1_000_000_000 },
+            InitializedTick { tick: 600, liquidity_net: -1_000_000_000 },
+        ];
+        let quote = quote_exact_input(
+            sqrt_price_x64, 0, 1_000_000_000, 100, &ticks, (-600, 660), 1_000_000, SwapDirection::ZeroForOne,
+        ).unwrap();
+        assert_eq!(quote.amount_in, 1_000_000);
+        assert!(quote.amount_out > 0 && quote.amount_out < 1_000_000);
+        assert!(quote.fee_amount > 0);
+        assert!(quote.price_impact > 0.0 && quote.price_impact < 0.01);
+        assert!(quote.sqrt_price_x64_after < sqrt_price_x64);
+    }
+
+    #[test]
+    fn test_quote_exact_input_beyond_tick_range() {
+        let sqrt_price_x64 = tick_math::get_sqrt_price_at_tick(0).unwrap();
+        let ticks = [InitializedTick { tick: 600, liquidity_net: -1_000 }];
+        let quote = quote_exact_input(
+            sqrt_price_x64, 0, 1_000, 100, &ticks, (0, 660), 1_000_000_000, SwapDirection::OneForZero,
+        );
+        assert!(quote.is_err());
+    }
+}
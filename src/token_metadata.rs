@@ -0,0 +1,64 @@
+use std::str::FromStr;
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+use crate::error::{BalanceFetcherError, Result};
+
+// Program ID for Solana mainnet.
+pub const TOKEN_METADATA_PROGRAM_ID: &str = "metaqbxxUerdq28cj1RJAWkwQm3vEBAXgese6D4ANyXa";
+
+// Metadata layout: key (1) + update_authority (32) + mint (32) + name (4 + len) + symbol (4 + len) + ...
+const NAME_OFFSET: usize = 1 + 32 + 32;
+
+/// Name and symbol of a mint from its Metaplex token metadata account
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TokenMetadata {
+    pub name: String,
+    pub symbol: String,
+}
+
+impl TokenMetadata {
+    /// Read from the data of a Metaplex metadata account, ignoring everything after the symbol
+    pub fn from_account_data(data: &[u8]) -> Result<Self> {
+        let (name, offset) = read_string(data, NAME_OFFSET)?;
+        let (symbol, _) = read_string(data, offset)?;
+        Ok(Self { name, symbol })
+    }
+}
+
+/// The address of the Metaplex metadata account of a mint
+pub fn metadata_address(mint: &Pubkey) -> Pubkey {
+    let program_id = Pubkey::from_str(TOKEN_METADATA_PROGRAM_ID).unwrap();
+    let seeds: &[&[u8]] = &[b"metadata", program_id.as_ref(), mint.as_ref()];
+    Pubkey::find_program_address(seeds, &program_id).0
+}
+
+/// Read a Borsh string at `offset`, returning it without the NUL padding Metaplex adds, and the offset after it
+fn read_string(data: &[u8], offset: usize) -> Result<(String, usize)> {
+    let too_short = || BalanceFetcherError::Deserialize(format!("metadata too short: {} bytes", data.len()));
+    let len_bytes = data.get(offset..offset + 4).ok_or_else(too_short)?;
+    let len = u32::from_le_bytes(len_bytes.try_into().unwrap()) as usize;
+    let start = offset + 4;
+    let bytes = data.get(start..start.saturating_add(len)).ok_or_else(too_short)?;
+    let string = String::from_utf8_lossy(bytes).trim_end_matches('\0').to_string();
+    Ok((string, start + len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn borsh_string(string: &str, padded_len: usize) -> Vec<u8> {
+        let mut bytes = string.as_bytes().to_vec();
+        bytes.resize(padded_len, 0);
+        [(padded_len as u32).to_le_bytes().to_vec(), bytes].concat()
+    }
+
+    #[test]
+    fn test_from_account_data() {
+        let data = [vec![4; NAME_OFFSET], borsh_string("USD Coin", 32), borsh_string("USDC", 10), vec![0; 8]].concat();
+        let metadata = TokenMetadata::from_account_data(&data).unwrap();
+        assert_eq!(metadata, TokenMetadata { name: "USD Coin".to_string(), symbol: "USDC".to_string() });
+        assert!(TokenMetadata::from_account_data(&data[..NAME_OFFSET + 40]).is_err());
+        assert!(TokenMetadata::from_account_data(&[]).is_err());
+    }
+}
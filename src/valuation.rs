@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+use crate::balance_fetcher::{BalanceFetcher, PoolPrice};
+use crate::error::Result;
+use crate::ids::{MintAddress, PoolId};
+use crate::pricing::PriceSource;
+use crate::report::{serialize_pubkey, serialize_pubkeys, Amount, WalletReport};
+
+/// USD prices of mints, derived from the current prices of Raydium pools
+///
+/// Pool prices move with every swap and a thin pool is cheap to push around, so these prices are for a rough idea of
+/// what holdings are worth rather than for accounting.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UsdPrices {
+    prices: HashMap<Pubkey, f64>,
+}
+
+impl UsdPrices {
+    /// Price USDC at $1, then the other token of each pool one of whose tokens is priced
+    ///
+    /// The pools are walked until no more token can be priced, so that a token paired with SOL is priced through the
+    /// SOL-USDC pool. A token is priced by the first pool reaching it.
+    pub fn from_pool_prices(pool_prices: &[PoolPrice]) -> Self {
+        let mut prices = HashMap::from([(MintAddress::usdc().pubkey(), 1.0)]);
+        loop {
+            let mut priced = false;
+            for pool_price in pool_prices {
+                let (mint, price) = match (prices.get(&pool_price.mint_0), prices.get(&pool_price.mint_1)) {
+                    (Some(price_0), None) => (pool_price.mint_1, price_0 * pool_price.price_1_in_0),
+                    (None, Some(price_1)) => (pool_price.mint_0, price_1 * pool_price.price_0_in_1),
+                    _ => continue,
+                };
+                // An empty pool has a zero price, whose inverse is infinite
+                if price.is_finite() && price > 0.0 {
+                    prices.insert(mint, price);
+                    priced = true;
+                }
+            }
+            if !priced {
+                return Self { prices };
+            }
+        }
+    }
+
+    /// Price `mints` by a price source such as an oracle, leaving out the mints it does not price
+    pub fn from_price_source<S: PriceSource>(price_source: &S, mints: &[MintAddress]) -> Result<Self> {
+        let prices = mints
+            .iter()
+            .zip(price_source.usd_prices(mints)?)
+            .filter_map(|(mint, price)| Some((mint.pubkey(), price?.price)))
+            .collect();
+        Ok(Self { prices })
+    }
+
+    /// The USD price of a whole token of `mint`, `None` if it is not priced
+    pub fn price(&self, mint: &Pubkey) -> Option<f64> {
+        self.prices.get(mint).copied()
+    }
+
+    /// The USD value of an amount of `mint`, `None` if it is not priced
+    pub fn value(&self, mint: &Pubkey, amount: &Amount) -> Option<f64> {
+        self.price(mint).map(|price| price * amount.ui_amount())
+    }
+}
+
+/// USD value of the balances and LP positions of a `WalletReport`
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct WalletValue {
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub address: Pubkey,
+    /// SOL and WSOL, both priced as WSOL
+    pub sol_unified_usd: Option<f64>,
+    pub positions: Vec<PositionValue>,
+    /// SOL, WSOL and the positions, leaving out the amounts of unpriced mints
+    pub total_usd: f64,
+    /// Mints held but not priced, e.g. a reward token without a pool to price it by
+    #[serde(serialize_with = "serialize_pubkeys")]
+    pub unpriced_mints: Vec<Pubkey>,
+}
+
+/// USD value of a `PositionReport`
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PositionValue {
+    /// The position PDA
+    #[serde(serialize_with = "serialize_pubkey")]
+    pub position: Pubkey,
+    /// Both tokens of the position and its unclaimed fees and pending rewards, leaving out unpriced mints
+    pub usd: f64,
+}
+
+impl WalletReport {
+    /// The mints of the wallet's balances and positions, WSOL for SOL, to price for `usd_value`
+    pub fn mints(&self) -> Vec<MintAddress> {
+        let mut mints = vec![MintAddress::wsol()];
+        for p in &self.positions {
+            for mint in [p.mint_0, p.mint_1].into_iter().chain(p.rewards.iter().map(|reward| reward.mint)) {
+                let mint = MintAddress::from(mint);
+                if !mints.contains(&mint) {
+                    mints.push(mint);
+                }
+            }
+        }
+        mints
+    }
+
+    /// The USD value of the wallet's balances and positions at `prices`
+    pub fn usd_value(&self, prices: &UsdPrices) -> WalletValue {
+        let mut unpriced_mints = Vec::new();
+        let mut value = |mint: &Pubkey, amount: &Amount| match prices.value(mint, amount) {
+            Some(value) => value,
+            None => {
+                if !unpriced_mints.contains(mint) {
+                    unpriced_mints.push(*mint);
+                }
+                0.0
+            }
+        };
+        let wsol_mint_address = MintAddress::wsol().pubkey();
+        let sol_unified_usd = prices.value(&wsol_mint_address, &self.sol_unified);
+        let positions: Vec<PositionValue> = self
+            .positions
+            .iter()
+            .map(|p| {
+                let fees = value(&p.mint_0, &p.unclaimed_fee_0) + value(&p.mint_1, &p.unclaimed_fee_1);
+                let rewards: f64 = p.rewards.iter().map(|reward| value(&reward.mint, &reward.amount)).sum();
+                let usd = value(&p.mint_0, &p.amount_0) + value(&p.mint_1, &p.amount_1) + fees + rewards;
+                PositionValue { position: p.position, usd }
+            })
+            .collect();
+        if sol_unified_usd.is_none() {
+            unpriced_mints.push(wsol_mint_address);
+        }
+        let total_usd = sol_unified_usd.unwrap_or_default() + positions.iter().map(|p| p.usd).sum::<f64>();
+        WalletValue { address: self.address, sol_unified_usd, positions, total_usd, unpriced_mints }
+    }
+}
+
+impl BalanceFetcher {
+    /// Fetch USD prices of SOL, USDC and the tokens of `pool_ids` from the current prices of the pools
+    ///
+    /// SOL is priced by the SOL-USDC.1bp pool, the tokens of the other pools by their pair token as in
+    /// `UsdPrices::from_pool_prices`.
+    ///
+    /// # Arguments
+    /// - `pool_ids` - More pools to price tokens by, on top of SOL-USDC.1bp
+    ///
+    /// # Returns
+    /// - `UsdPrices` - The prices of the tokens that could be priced
+    pub fn usd_prices(&self, pool_ids: &[PoolId]) -> Result<UsdPrices> {
+        let pool_prices = std::iter::once(&PoolId::sol_usdc_1bp())
+            .chain(pool_ids)
+            .map(|pool_id| self.pool_price(pool_id))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(UsdPrices::from_pool_prices(&pool_prices))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raydium_amm_v3::states::{PersonalPositionState, PoolState, TickState};
+    use crate::math;
+    use crate::report::{position_report, PositionReport, RewardAmount};
+
+    fn pool_price(mint_0: Pubkey, mint_1: Pubkey, price_0_in_1: f64) -> PoolPrice {
+        PoolPrice {
+            pool_id: Pubkey::new_unique(),
+            mint_0,
+            mint_1,
+            decimals_0: 9,
+            decimals_1: 6,
+            sqrt_price_x64: 0,
+            tick_current: 0,
+            price_0_in_1,
+            price_1_in_0: 1.0 / price_0_in_1,
+        }
+    }
+
+    #[test]
+    fn test_from_pool_prices() {
+        let (wsol, usdc) = (MintAddress::wsol().pubkey(), MintAddress::usdc().pubkey());
+        let (ray, unpriced) = (Pubkey::new_unique(), Pubkey::new_unique());
+        // RAY priced in SOL comes before the SOL-USDC pool pricing SOL
+        let prices = UsdPrices::from_pool_prices(&[
+            pool_price(ray, wsol, 0.01),
+            pool_price(wsol, usdc, 150.0),
+            pool_price(unpriced, Pubkey::new_unique(), 2.0),
+            pool_price(Pubkey::new_unique(), usdc, 0.0),
+        ]);
+        assert_eq!(prices.price(&usdc), Some(1.0));
+        assert_eq!(prices.price(&wsol), Some(150.0));
+        assert!((prices.price(&ray).unwrap() - 1.5).abs() < 1e-9);
+        assert_eq!(prices.price(&unpriced), None);
+        assert_eq!(prices.value(&wsol, &Amount { amount: 2_000_000_000, decimals: 9 }), Some(300.0));
+    }
+
+    #[test]
+    fn test_usd_value() {
+        let (wsol, usdc) = (MintAddress::wsol().pubkey(), MintAddress::usdc().pubkey());
+        let prices = UsdPrices::from_pool_prices(&[pool_price(wsol, usdc, 100.0)]);
+        let reward_mint = Pubkey::new_unique();
+        let sol = |amount| Amount { amount, decimals: 9 };
+        let usd = |amount| Amount { amount, decimals: 6 };
+        let position = PositionReport {
+            position: Pubkey::new_unique(),
+            nft_mint: Pubkey::new_unique(),
+            tick_lower_index: -10,
+            tick_upper_index: 10,
+            in_range: true,
+            mint_0: wsol,
+            amount_0: sol(1_000_000_000),
+            mint_1: usdc,
+            amount_1: usd(50_000_000),
+            unclaimed_fee_0: sol(10_000_000),
+            unclaimed_fee_1: usd(1_000_000),
+            rewards: vec![RewardAmount { mint: reward_mint, amount: usd(5) }],
+        };
+        let report = WalletReport {
+            address: Pubkey::new_unique(),
+            slot: 1,
+            sol: sol(500_000_000),
+            wsol: sol(0),
+            sol_unified: sol(500_000_000),
+            pool_id: PoolId::sol_usdc_1bp().pubkey(),
+            positions: vec![position],
+            duplicate_ranges: vec![],
+        };
+        let value = report.usd_value(&prices);
+        assert_eq!(value.sol_unified_usd, Some(50.0));
+        assert!((value.positions[0].usd - 152.0).abs() < 1e-9);
+        assert!((value.total_usd - 202.0).abs() < 1e-9);
+        assert_eq!(value.unpriced_mints, vec![reward_mint]);
+    }
+
+    #[test]
+    fn test_usd_value_out_of_range() {
+        let (wsol, usdc) = (MintAddress::wsol().pubkey(), MintAddress::usdc().pubkey());
+        let prices = UsdPrices::from_pool_prices(&[pool_price(wsol, usdc, 100.0)]);
+        let pool_state = PoolState {
+            token_mint_0: wsol,
+            token_mint_1: usdc,
+            mint_decimals_0: 9,
+            mint_decimals_1: 6,
+            sqrt_price_x64: math::sqrt_price_at_tick(0).unwrap(),
+            ..PoolState::default()
+        };
+        let ticks = TickState::default();
+        let position = |tick_lower_index, tick_upper_index| {
+            let state = PersonalPositionState {
+                tick_lower_index,
+                tick_upper_index,
+                liquidity: 1_000_000_000_000,
+                ..PersonalPositionState::default()
+            };
+            position_report(Pubkey::new_unique(), &state, &pool_state, &ticks, &ticks, &HashMap::new(), 0).unwrap()
+        };
+        // Above the current price, the position holds token 0 only, not both tokens of its full range
+        let (in_range, above) = (position(-100, 100), position(100, 200));
+        assert_eq!(above.amount_1.amount, 0);
+        let report = WalletReport {
+            address: Pubkey::new_unique(),
+            slot: 1,
+            sol: Amount { amount: 0, decimals: 9 },
+            wsol: Amount { amount: 0, decimals: 9 },
+            sol_unified: Amount { amount: 0, decimals: 9 },
+            pool_id: PoolId::sol_usdc_1bp().pubkey(),
+            positions: vec![in_range.clone(), above.clone()],
+            duplicate_ranges: vec![],
+        };
+        let value = report.usd_value(&prices);
+        let in_range_usd = 100.0 * in_range.amount_0.ui_amount() + in_range.amount_1.ui_amount();
+        assert!((value.positions[0].usd - in_range_usd).abs() < 1e-9);
+        assert!((value.positions[1].usd - 100.0 * above.amount_0.ui_amount()).abs() < 1e-9);
+        assert!((value.total_usd - value.positions[0].usd - value.positions[1].usd).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_usd_prices() {
+        let fetcher = BalanceFetcher::new("https://api.mainnet-beta.solana.com");
+        let prices = fetcher.usd_prices(&[]).unwrap();
+        assert!(prices.price(&MintAddress::wsol()).unwrap() > 0.0);
+        assert_eq!(prices.price(&MintAddress::usdc()), Some(1.0));
+    }
+}